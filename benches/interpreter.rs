@@ -0,0 +1,106 @@
+// MIT License
+//
+// Copyright (C) 2015-2018 Steve Sprang
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::hint::black_box;
+use std::io;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use malbolge::{Config, Interpreter, MAX_MEMORY};
+
+fn crazy_op(c: &mut Criterion) {
+    c.bench_function("crazy_op", |b| b.iter(|| {
+        (0..MAX_MEMORY).step_by(97).fold(0, |acc, x| {
+            malbolge::crazy_op(black_box(x), black_box(acc))
+        })
+    }));
+}
+
+fn tri_rotate(c: &mut Criterion) {
+    c.bench_function("tri_rotate", |b| b.iter(|| {
+        (0..MAX_MEMORY).step_by(97).fold(0, |acc, x| {
+            acc ^ malbolge::tri_rotate(black_box(x))
+        })
+    }));
+}
+
+fn run_hello_world(c: &mut Criterion) {
+    let source = include_bytes!("../programs/hello-world.mb");
+
+    c.bench_function("hello-world", |b| b.iter(|| {
+        let mut interpreter = Interpreter::new(black_box(source)).unwrap();
+        interpreter.execute(&mut &b"fixed input"[..], &mut io::sink()).unwrap()
+    }));
+}
+
+fn run_99bottles(c: &mut Criterion) {
+    let source = include_bytes!("../programs/99bottles.mb");
+
+    c.bench_function("99bottles", |b| b.iter(|| {
+        let mut interpreter = Interpreter::new(black_box(source)).unwrap();
+        interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap()
+    }));
+}
+
+fn run_busy_loop(c: &mut Criterion) {
+    // see tests/programs.rs; if the instruction count changes, so has the
+    // interpreter's behaviour
+    const INSTRUCTIONS: u64 = 590_731;
+
+    let interpreter = Interpreter::new(include_bytes!("../tests/fixtures/busy-loop.mb")).unwrap();
+    let mut group = c.benchmark_group("busy-loop");
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+
+    // loading copies all of memory, so leave it out of the measurement
+    group.bench_function("instructions", |b| b.iter_batched(
+        || interpreter.clone(),
+        |mut interpreter| {
+            interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap();
+            assert_eq!(interpreter.instruction_count(), INSTRUCTIONS);
+        },
+        BatchSize::SmallInput,
+    ));
+
+    group.finish();
+}
+
+fn run_step_limited(c: &mut Criterion) {
+    // copy.mb echoes forever once its input runs out, which makes it a
+    // convenient CPU-bound loop
+    let source = include_bytes!("../programs/copy.mb");
+    let config = Config { max_steps: Some(1_000_000), ..Config::default() };
+
+    c.bench_function("copy 1M steps", |b| b.iter(|| {
+        let mut interpreter = Interpreter::with_config(black_box(source),
+                                                       config.clone()).unwrap();
+        interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap()
+    }));
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = crazy_op, tri_rotate, run_hello_world, run_99bottles,
+              run_busy_loop, run_step_limited
+}
+criterion_main!(benches);
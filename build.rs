@@ -0,0 +1,45 @@
+// MIT License
+//
+// Copyright (C) 2015-2018 Steve Sprang
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    // tests/ffi.rs links against this to check include/malbolge.h; only
+    // the search path is passed on, so nothing else links it
+    #[cfg(feature = "ffi")]
+    {
+        println!("cargo:rerun-if-changed=include/malbolge.h");
+        println!("cargo:rerun-if-changed=tests/ffi.c");
+
+        cc::Build::new()
+            .file("tests/ffi.c")
+            .include("include")
+            .warnings(true)
+            .warnings_into_errors(true)
+            .cargo_metadata(false)
+            .compile("malbolge_ffi_test");
+
+        println!("cargo:rustc-link-search=native={}", std::env::var("OUT_DIR").unwrap());
+    }
+}
@@ -0,0 +1,67 @@
+// MIT License
+//
+// Copyright (C) 2015-2018 Steve Sprang
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Runs every program in `programs/` in parallel, each with its own input,
+//! output and step limit.
+//!
+//!     cargo run --release --example batch
+
+use std::fs;
+
+use malbolge::{Config, Interpreter};
+use rayon::prelude::*;
+
+const MAX_STEPS: u64 = 100_000_000;
+
+fn main() {
+    let mut programs: Vec<(String, Vec<u8>)> = fs::read_dir("programs")
+        .expect("run this from the root of the repository")
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            Some((path.display().to_string(), fs::read(&path).ok()?))
+        })
+        .collect();
+
+    programs.sort();
+
+    let results: Vec<_> = programs.par_iter().map(|(name, source)| {
+        let config = Config { max_steps: Some(MAX_STEPS), ..Config::default() };
+        let mut interpreter = match Interpreter::with_config(source, config) {
+            Ok(interpreter) => interpreter,
+            Err(why) => return (name, why.to_string(), 0),
+        };
+
+        let mut input = &b"Hello, rayon!"[..];
+        let mut output = Vec::new();
+
+        match interpreter.execute(&mut input, &mut output) {
+            Ok(exit) => (name, exit.halt.to_string(), output.len()),
+            Err(e) => (name, e.to_string(), output.len()),
+        }
+    }).collect();
+
+    for (name, result, len) in results {
+        println!("{}: {} ({} bytes of output)", name, result, len);
+    }
+}
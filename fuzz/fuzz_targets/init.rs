@@ -0,0 +1,46 @@
+// MIT License
+//
+// Copyright (C) 2015-2018 Steve Sprang
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Feeds arbitrary bytes to the loader. Any input must either load or be
+//! rejected with an `InitError`; a panic is a bug.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use malbolge::{InitError, MAX_MEMORY};
+
+fuzz_target!(|data: &[u8]| {
+    let mut mem: Box<malbolge::Memory> = vec![0; MAX_MEMORY].try_into().unwrap();
+
+    match malbolge::init(data, &mut mem) {
+        Ok(len) => {
+            assert!((2..=MAX_MEMORY).contains(&len));
+            assert!(mem.iter().all(|&cell| (cell as usize) < MAX_MEMORY));
+        }
+        Err(InitError::InvalidChar { offset, index, .. }) => {
+            assert!(index <= offset && offset < data.len());
+        }
+        Err(InitError::SourceEmpty | InitError::SourceTooShort | InitError::SourceTooLong) => {}
+    }
+});
@@ -0,0 +1,51 @@
+/*
+ * C interface to the Malbolge interpreter. Build the library with
+ * `cargo rustc --release --lib --features ffi --crate-type staticlib`
+ * (or `cdylib`) and link against libmalbolge.a or libmalbolge.so.
+ */
+
+#ifndef MALBOLGE_H
+#define MALBOLGE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A pointer argument was NULL but its length wasn't zero. */
+#define MAL_ERR_NULL       (-1)
+/* The source program could not be loaded. */
+#define MAL_ERR_INIT       (-2)
+/* The program wrote more than out_cap bytes. */
+#define MAL_ERR_OUTPUT     (-3)
+/* Writing the output failed for some other reason. */
+#define MAL_ERR_IO         (-4)
+/* The program ran for max_steps instructions without halting. Only
+ * mal_run_limited returns this. */
+#define MAL_ERR_STEP_LIMIT (-5)
+
+/*
+ * Runs a Malbolge program until it halts, feeding it `input` and writing
+ * its output to `out`. Returns the number of bytes written, or one of the
+ * negative MAL_ERR_* codes. All memory belongs to the caller.
+ */
+intptr_t mal_run(const unsigned char *source, size_t source_len,
+                 const unsigned char *input, size_t input_len,
+                 unsigned char *out, size_t out_cap);
+
+/*
+ * Like mal_run, but stops with MAL_ERR_STEP_LIMIT after `max_steps`
+ * instructions, or runs with no limit if it is 0.
+ */
+intptr_t mal_run_limited(const unsigned char *source, size_t source_len,
+                         const unsigned char *input, size_t input_len,
+                         unsigned char *out, size_t out_cap,
+                         uint64_t max_steps);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* MALBOLGE_H */
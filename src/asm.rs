@@ -0,0 +1,233 @@
+// MIT License
+//
+// Copyright (C) 2015-2018 Steve Sprang
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! An assembler that turns a list of opcode mnemonics into a program that
+//! `init` will load.
+//!
+//! | Mnemonic | Opcode |
+//! |----------|--------|
+//! | `jmp`    | `i`    |
+//! | `movd`   | `j`    |
+//! | `rot`    | `*`    |
+//! | `crz`    | `p`    |
+//! | `out`    | `<`    |
+//! | `in`     | `/`    |
+//! | `hlt`    | `v`    |
+//! | `nop`    | `o`    |
+//!
+//! `assemble_normalized` takes the opcodes themselves instead, written out
+//! as text.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::error;
+use core::fmt;
+
+use crate::{encode, MAX_MEMORY, OPCODES};
+
+////////////////////////////////////////////////////////////////////////////////
+// AsmError
+////////////////////////////////////////////////////////////////////////////////
+
+/// Why a list of mnemonics could not be assembled.
+#[derive(Debug, PartialEq, Eq)]
+pub enum AsmError {
+    /// The mnemonic at the given index isn't one of the eight above.
+    UnknownMnemonic(String, usize),
+    /// A character in the text given to `assemble_normalized` isn't one
+    /// of the `OPCODES`. The line and column count from one, and columns
+    /// count characters.
+    UnknownToken { c: char, line: usize, column: usize },
+    /// There are more instructions than fit in memory.
+    ProgramTooLong,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AsmError::UnknownMnemonic(ref m, index) =>
+                write!(f, "Unknown mnemonic '{}' at index {}", m, index),
+            AsmError::UnknownToken { c, line, column } =>
+                write!(f, "Unknown token '{}' at line {}, column {}", c, line, column),
+            AsmError::ProgramTooLong => write!(f, "Program is too long."),
+        }
+    }
+}
+
+impl error::Error for AsmError {}
+
+////////////////////////////////////////////////////////////////////////////////
+// Assembler
+////////////////////////////////////////////////////////////////////////////////
+
+/// Returns the opcode that `mnemonic` stands for.
+pub fn opcode(mnemonic: &str) -> Option<char> {
+    let op = match mnemonic {
+        "jmp" => 'i',
+        "movd" => 'j',
+        "rot" => '*',
+        "crz" => 'p',
+        "out" => '<',
+        "in" => '/',
+        "hlt" => 'v',
+        "nop" => 'o',
+        _ => return None,
+    };
+
+    Some(op)
+}
+
+/// Encrypts `mnemonics` into source bytes, each chosen so that it decodes
+/// to the requested opcode at its position in memory. Note that `init`
+/// rejects programs shorter than two instructions.
+pub fn assemble(mnemonics: &[&str]) -> Result<Vec<u8>, AsmError> {
+    if mnemonics.len() > MAX_MEMORY {
+        return Err(AsmError::ProgramTooLong);
+    }
+
+    mnemonics.iter().enumerate().map(|(addr, &mnemonic)| {
+        let op = opcode(mnemonic)
+            .ok_or_else(|| AsmError::UnknownMnemonic(mnemonic.to_string(), addr))?;
+
+        // every opcode is in XLAT1
+        Ok(encode(op, addr).unwrap())
+    }).collect()
+}
+
+/// Encrypts a program written in normalized form, one of the `OPCODES`
+/// characters per instruction, into source bytes as `assemble` does.
+/// Whitespace only separates instructions, and everything from a `;` to
+/// the end of its line is a comment.
+///
+/// ```
+/// use malbolge::asm::assemble_normalized;
+///
+/// let source = assemble_normalized("jj ; load\n< v ; print and stop\n").unwrap();
+/// assert_eq!(source.len(), 4);
+/// ```
+pub fn assemble_normalized(text: &str) -> Result<Vec<u8>, AsmError> {
+    let mut source = Vec::new();
+
+    for (n, line) in text.lines().enumerate() {
+        let code = line.split(';').next().unwrap_or("");
+
+        for (column, c) in code.chars().enumerate() {
+            if c.is_whitespace() {
+                continue;
+            }
+
+            if !OPCODES.contains(c) {
+                return Err(AsmError::UnknownToken { c, line: n + 1, column: column + 1 });
+            }
+
+            if source.len() >= MAX_MEMORY {
+                return Err(AsmError::ProgramTooLong);
+            }
+
+            source.push(encode(c, source.len()).unwrap());
+        }
+    }
+
+    Ok(source)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Tests
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Interpreter, OPCODES};
+
+    #[test]
+    fn round_trip_test() {
+        let mnemonics = ["movd", "jmp", "rot", "crz", "out", "in", "hlt", "nop"];
+        let program: Vec<&str> = mnemonics.iter().copied().cycle().take(200).collect();
+
+        let source = assemble(&program).unwrap();
+        let interpreter = Interpreter::new(&source).unwrap();
+        let ops: String = interpreter.disassemble(0, program.len())
+            .into_iter()
+            .map(|(_, op)| op)
+            .collect();
+
+        let expected: String = OPCODES.chars().cycle().take(200).collect();
+        assert_eq!(ops, expected);
+    }
+
+    #[test]
+    fn hello_world_test() {
+        let source = include_bytes!("../tests/fixtures/hello-world.mb");
+        let interpreter = Interpreter::new(source).unwrap();
+
+        let program: Vec<&str> = interpreter.disassemble(0, 131).into_iter().map(|(_, op)| {
+            match op {
+                'i' => "jmp", 'j' => "movd", '*' => "rot", 'p' => "crz",
+                '<' => "out", '/' => "in", 'v' => "hlt", _ => "nop",
+            }
+        }).collect();
+
+        let source = assemble(&program).unwrap();
+        let mut interpreter = Interpreter::new(&source).unwrap();
+        let mut output = Vec::new();
+        interpreter.execute(&mut &b""[..], &mut output).unwrap();
+        assert_eq!(output, b"Hello World!");
+    }
+
+    #[test]
+    fn error_test() {
+        let result = assemble(&["nop", "push"]);
+        assert_eq!(result, Err(AsmError::UnknownMnemonic("push".to_string(), 1)));
+
+        let program = vec!["nop"; MAX_MEMORY + 1];
+        assert_eq!(assemble(&program), Err(AsmError::ProgramTooLong));
+    }
+
+    #[test]
+    fn assemble_normalized_test() {
+        let source = include_bytes!("../tests/fixtures/hello-world.mb");
+        let normalized = crate::to_normalized(source).unwrap();
+
+        // split it over commented lines of 40 instructions
+        let mut text = String::new();
+        for (i, chunk) in normalized.as_bytes().chunks(40).enumerate() {
+            text.push_str(core::str::from_utf8(chunk).unwrap());
+            text.push_str(&alloc::format!("\t; part {}\r\n", i));
+        }
+
+        let assembled = assemble_normalized(&text).unwrap();
+        let mut interpreter = Interpreter::new(&assembled).unwrap();
+        let mut output = Vec::new();
+        interpreter.execute(&mut &b""[..], &mut output).unwrap();
+        assert_eq!(output, b"Hello World!");
+
+        assert_eq!(assemble_normalized("jj\n  <x v"),
+                   Err(AsmError::UnknownToken { c: 'x', line: 2, column: 4 }));
+        assert_eq!(assemble_normalized("; just a comment"), Ok(Vec::new()));
+
+        let text: String = core::iter::repeat_n('o', MAX_MEMORY + 1).collect();
+        assert_eq!(assemble_normalized(&text), Err(AsmError::ProgramTooLong));
+    }
+}
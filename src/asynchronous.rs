@@ -0,0 +1,262 @@
+// MIT License
+//
+// Copyright (C) 2015-2018 Steve Sprang
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Running programs on async streams, built with the `async` feature.
+//!
+//! The streams are tokio's `AsyncRead` and `AsyncWrite`, so sockets, pipes
+//! and `tokio::io::duplex` can be passed in as they are. Streams written
+//! for `futures::io` can be adapted with `tokio_util::compat`.
+
+use core::future::{poll_fn, Future};
+use core::task::Poll;
+use std::io;
+use std::time::Instant;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{Config, Exit, FlushPolicy, Halt, Interpreter, OutputMode, StepResult::*};
+
+/// How many instructions `run_async` executes before giving other tasks a
+/// chance to run.
+pub const YIELD_INTERVAL: u64 = 1 << 12;
+
+impl Interpreter {
+    /// Like `execute`, but awaits `input` at each `/` and `output` at each
+    /// `<`, and yields every `YIELD_INTERVAL` instructions so that a long
+    /// computation doesn't starve other tasks. Output is flushed before
+    /// waiting for input and once the program halts.
+    ///
+    /// `Config::max_steps`, `max_output`, `max_duration`, `output_mode`,
+    /// `FlushPolicy::EveryByte` and breakpoints apply as they do for
+    /// `execute`, and so does the handling of read errors. Loop detection,
+    /// tracing and `step_delay` are left to the blocking version.
+    pub async fn run_async<R, W>(&mut self, mut input: R, mut output: W) -> io::Result<Exit>
+        where R: AsyncRead + Unpin,
+              W: AsyncWrite + Unpin
+    {
+        let Config { max_steps, max_output, max_duration, output_mode, flush_policy, .. } = self.config;
+        let deadline = max_duration.map(|max| Instant::now() + max);
+        let mut steps = 0;
+        let mut written = 0;
+
+        loop {
+            if max_steps.is_some_and(|max| steps >= max) {
+                return Ok(self.exit(Halt::StepLimitReached));
+            }
+
+            if steps > 0 && steps % YIELD_INTERVAL == 0 {
+                if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    return Ok(self.exit(Halt::Timeout));
+                }
+
+                yield_now().await;
+            }
+
+            if steps > 0 && self.breakpoints.contains(&self.r_c) {
+                return Ok(self.exit(Halt::Breakpoint(self.r_c)));
+            }
+
+            if max_output.is_some_and(|max| written >= max) && self.at_output() {
+                output.flush().await?;
+                return Ok(self.exit(Halt::OutputLimitReached));
+            }
+
+            steps += 1;
+
+            match self.step() {
+                Continue | MemoryChanged { .. } => {}
+                Output(b) => {
+                    written += 1;
+                    self.bytes_output += 1;
+
+                    let mut buf = [b; 4];
+                    let bytes = match output_mode {
+                        OutputMode::Raw => &buf[..1],
+                        OutputMode::Utf8Lossy => (b as char).encode_utf8(&mut buf).as_bytes(),
+                    };
+
+                    output.write_all(bytes).await?;
+
+                    if flush_policy == FlushPolicy::EveryByte {
+                        output.flush().await?;
+                    }
+
+                    self.remember_output(b);
+                }
+                NeedsInput => {
+                    // a prompt should arrive before the program waits
+                    output.flush().await?;
+
+                    let mut buf = [0u8];
+                    let n = loop {
+                        match input.read(&mut buf).await {
+                            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                            read => break read?,
+                        }
+                    };
+                    self.input(if n == 1 { Some(buf[0]) } else { None });
+                }
+                Halted(halt) => {
+                    output.flush().await?;
+                    return Ok(self.exit(halt));
+                }
+            }
+        }
+    }
+}
+
+/// Returns `Poll::Pending` once, after asking to be polled again. Unlike
+/// `tokio::task::yield_now` it doesn't need a tokio runtime.
+fn yield_now() -> impl Future<Output = ()> {
+    let mut yielded = false;
+
+    poll_fn(move |cx| {
+        if yielded {
+            Poll::Ready(())
+        } else {
+            yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    })
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Tests
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InterpreterBuilder;
+    use core::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Wake, Waker};
+    use tokio::io::ReadBuf;
+
+    struct Noop;
+
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    /// Polls `future` until it's ready, returning how many times it wasn't.
+    fn block_on<F: Future>(future: F) -> (F::Output, usize) {
+        let waker = Waker::from(Arc::new(Noop));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = core::pin::pin!(future);
+        let mut pending = 0;
+
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return (output, pending),
+                Poll::Pending => pending += 1,
+            }
+        }
+    }
+
+    /// Hands out one byte at a time, each only on the second poll.
+    struct Slow<'a>(&'a [u8], bool);
+
+    impl AsyncRead for Slow<'_> {
+        fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>)
+            -> Poll<io::Result<()>> {
+            self.1 = !self.1;
+
+            if self.1 {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+
+            let n = self.0.len().min(buf.remaining()).min(1);
+            buf.put_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn run_async_test() {
+        let source = include_bytes!("../tests/fixtures/hello-world.mb");
+        let mut interpreter = Interpreter::new(source).unwrap();
+        let mut output = Vec::new();
+
+        let (exit, _) = block_on(interpreter.run_async(&b""[..], &mut output));
+        assert_eq!(exit.unwrap().halt, Halt::Stopped);
+        assert_eq!(output, b"Hello World!");
+
+        let mut interpreter = InterpreterBuilder::new()
+            .max_output(4)
+            .build(include_bytes!("../tests/fixtures/cat.mb"))
+            .unwrap();
+        let mut output = Vec::new();
+
+        let (exit, pending) = block_on(interpreter.run_async(Slow(b"abcdef", false), &mut output));
+        assert_eq!(exit.unwrap().halt, Halt::OutputLimitReached);
+        assert_eq!(output, b"abcd");
+        assert!(pending >= 5);
+    }
+
+    #[test]
+    fn run_async_yield_test() {
+        let mut interpreter = InterpreterBuilder::new()
+            .max_steps(3 * YIELD_INTERVAL)
+            .build(include_bytes!("../programs/copy.mb"))
+            .unwrap();
+
+        // copy.mb spins forever once input runs out
+        // tokio::spawn needs the future to be Send
+        let future = interpreter.run_async(&b""[..], Vec::new());
+        fn assert_send<T: Send>(_: &T) {}
+        assert_send(&future);
+
+        let (exit, pending) = block_on(future);
+        assert_eq!(exit.unwrap().halt, Halt::StepLimitReached);
+        assert_eq!(pending, 2);
+        assert_eq!(interpreter.instruction_count(), 3 * YIELD_INTERVAL);
+    }
+
+    #[tokio::test]
+    async fn run_async_duplex_test() {
+        let (mut client, server) = tokio::io::duplex(64);
+        let (server_read, server_write) = tokio::io::split(server);
+        let mut interpreter = InterpreterBuilder::new()
+            .max_output(6)
+            .build(include_bytes!("../tests/fixtures/cat.mb"))
+            .unwrap();
+
+        let session = async {
+            // cat.mb echoes end of input too, which is past the limit
+            client.write_all(b"Hello!").await.unwrap();
+            client.shutdown().await.unwrap();
+            let mut echoed = [0; 6];
+            client.read_exact(&mut echoed).await.unwrap();
+            echoed
+        };
+
+        let (exit, echoed) = tokio::join!(interpreter.run_async(server_read, server_write), session);
+        assert_eq!(exit.unwrap().halt, Halt::OutputLimitReached);
+        assert_eq!(&echoed, b"Hello!");
+    }
+}
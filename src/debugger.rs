@@ -0,0 +1,135 @@
+// MIT License
+//
+// Copyright (C) 2015-2018 Steve Sprang
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! The interactive debugger behind the `--debug` option.
+
+use std::io::{self, Read, Write};
+
+use malbolge::{Interpreter, StepResult, Trits, MAX_MEMORY};
+
+const HELP: &str = "Commands: step, continue, regs, mem ADDR, break ADDR, \
+                    disasm [START COUNT], quit";
+
+/// Reads commands from stdin and applies them to `interpreter` until the
+/// user quits. The program's `/` instruction reads from `input`. With
+/// `trits`, cells are shown in base 3.
+pub fn run(interpreter: &mut Interpreter, input: &mut dyn Read, trits: bool) {
+    let stdin = io::stdin();
+    let mut line = String::new();
+
+    println!("{}", HELP);
+    show_next(interpreter, trits);
+
+    loop {
+        print!("(mdb) ");
+        let _ = io::stdout().flush();
+
+        line.clear();
+        match stdin.read_line(&mut line) {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        match words[..] {
+            [] => {}
+            ["step"] | ["s"] => step(interpreter, input, trits),
+            ["continue"] | ["c"] => {
+                match interpreter.execute(input, &mut io::stdout()) {
+                    Ok(exit) => println!("{}", exit.halt),
+                    Err(e) => println!("{}", e),
+                }
+
+                show_next(interpreter, trits);
+            }
+            ["regs"] => {
+                let (r_a, r_c, r_d) = interpreter.registers();
+                println!("r_a = {}  r_c = {}  r_d = {}", r_a, r_c, r_d);
+            }
+            ["mem", addr] => match parse_addr(addr) {
+                Some(addr) => show_cell(interpreter, addr, trits),
+                None => println!("Invalid address: {}", addr),
+            },
+            ["break", addr] => match parse_addr(addr) {
+                Some(addr) => interpreter.add_breakpoint(addr),
+                None => println!("Invalid address: {}", addr),
+            },
+            ["disasm"] => disasm(interpreter, interpreter.registers().1, 10),
+            ["disasm", start, count] => match (parse_addr(start), count.parse()) {
+                (Some(start), Ok(count)) => disasm(interpreter, start, count),
+                _ => println!("{}", HELP),
+            },
+            ["quit"] | ["q"] => return,
+            _ => println!("{}", HELP),
+        }
+    }
+}
+
+fn step(interpreter: &mut Interpreter, input: &mut dyn Read, trits: bool) {
+    match interpreter.step() {
+        StepResult::Continue | StepResult::MemoryChanged { .. } => {}
+        StepResult::Output(b) => {
+            let mut stdout = io::stdout();
+            let _ = stdout.write_all(&[b]).and_then(|_| stdout.flush());
+        }
+        StepResult::NeedsInput => {
+            let mut buf = [0u8];
+
+            match input.read(&mut buf) {
+                Ok(1) => interpreter.input(Some(buf[0])),
+                Ok(_) => interpreter.input(None),
+                Err(e) => println!("{}", e),
+            }
+        }
+        StepResult::Halted(halt) => println!("{}", halt),
+    }
+
+    show_next(interpreter, trits);
+}
+
+fn show_next(interpreter: &Interpreter, trits: bool) {
+    show_cell(interpreter, interpreter.registers().1, trits);
+}
+
+fn show_cell(interpreter: &Interpreter, addr: usize, trits: bool) {
+    let (_, op) = interpreter.disassemble(addr, 1)[0];
+    let value = interpreter.mem(addr);
+
+    if trits {
+        println!("{:>5}: {}  {}", addr, Trits(value), op);
+    } else {
+        println!("{:>5}: {:>5}  {}", addr, value, op);
+    }
+}
+
+fn disasm(interpreter: &Interpreter, start: usize, count: usize) {
+    for (addr, op) in interpreter.disassemble(start, count) {
+        println!("{:>5}  {}", addr, op);
+    }
+}
+
+fn parse_addr(arg: &str) -> Option<usize> {
+    arg.parse().ok().filter(|&addr| addr < MAX_MEMORY)
+}
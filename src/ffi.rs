@@ -0,0 +1,126 @@
+// MIT License
+//
+// Copyright (C) 2015-2018 Steve Sprang
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! C bindings, built with the `ffi` feature. See `include/malbolge.h`.
+
+use std::io::ErrorKind;
+use std::slice;
+
+use crate::{Config, Halt, Interpreter};
+
+/// A pointer argument was null but its length wasn't zero.
+pub const MAL_ERR_NULL: isize = -1;
+/// The source program could not be loaded.
+pub const MAL_ERR_INIT: isize = -2;
+/// The program wrote more than `out_cap` bytes.
+pub const MAL_ERR_OUTPUT: isize = -3;
+/// Writing the output failed for some other reason.
+pub const MAL_ERR_IO: isize = -4;
+/// The program ran for `max_steps` instructions without halting. Only
+/// `mal_run_limited` returns this.
+pub const MAL_ERR_STEP_LIMIT: isize = -5;
+
+/// Runs the `source_len` bytes at `source` until the program halts,
+/// feeding it the `input_len` bytes at `input` and writing its output to
+/// the `out_cap` bytes at `out`. Returns the number of bytes written, or
+/// one of the negative `MAL_ERR_*` codes. All memory belongs to the caller.
+///
+/// # Safety
+///
+/// Each pointer must be valid for its length, or may be null if its
+/// length is zero. `out` must not overlap `source` or `input`.
+#[no_mangle]
+pub unsafe extern "C" fn mal_run(source: *const u8,
+                                 source_len: usize,
+                                 input: *const u8,
+                                 input_len: usize,
+                                 out: *mut u8,
+                                 out_cap: usize) -> isize {
+    run(source, source_len, input, input_len, out, out_cap, None)
+}
+
+/// Like `mal_run`, but stops with `MAL_ERR_STEP_LIMIT` after `max_steps`
+/// instructions, or runs with no limit if it is 0.
+///
+/// # Safety
+///
+/// The same as for `mal_run`.
+#[no_mangle]
+pub unsafe extern "C" fn mal_run_limited(source: *const u8,
+                                         source_len: usize,
+                                         input: *const u8,
+                                         input_len: usize,
+                                         out: *mut u8,
+                                         out_cap: usize,
+                                         max_steps: u64) -> isize {
+    let max_steps = if max_steps == 0 { None } else { Some(max_steps) };
+    run(source, source_len, input, input_len, out, out_cap, max_steps)
+}
+
+unsafe fn run(source: *const u8,
+              source_len: usize,
+              input: *const u8,
+              input_len: usize,
+              out: *mut u8,
+              out_cap: usize,
+              max_steps: Option<u64>) -> isize {
+    let (source, mut input) = match (bytes(source, source_len), bytes(input, input_len)) {
+        (Some(source), Some(input)) => (source, input),
+        _ => return MAL_ERR_NULL,
+    };
+
+    let out = if out_cap == 0 {
+        &mut []
+    } else if out.is_null() {
+        return MAL_ERR_NULL;
+    } else {
+        slice::from_raw_parts_mut(out, out_cap)
+    };
+
+    let config = Config { max_steps, ..Config::default() };
+
+    let mut interpreter = match Interpreter::with_config(source, config) {
+        Ok(interpreter) => interpreter,
+        Err(_) => return MAL_ERR_INIT,
+    };
+
+    let mut remaining = &mut out[..];
+
+    match interpreter.execute(&mut input, &mut remaining) {
+        Ok(exit) if exit.halt == Halt::StepLimitReached => MAL_ERR_STEP_LIMIT,
+        Ok(_) => (out_cap - remaining.len()) as isize,
+        Err(ref e) if e.kind() == ErrorKind::WriteZero => MAL_ERR_OUTPUT,
+        Err(_) => MAL_ERR_IO,
+    }
+}
+
+unsafe fn bytes<'a>(ptr: *const u8, len: usize) -> Option<&'a [u8]> {
+    if len == 0 {
+        Some(&[])
+    } else if ptr.is_null() {
+        None
+    } else {
+        Some(slice::from_raw_parts(ptr, len))
+    }
+}
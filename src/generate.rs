@@ -0,0 +1,200 @@
+// MIT License
+//
+// Copyright (C) 2015-2018 Steve Sprang
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! A generator for programs that print a fixed string.
+//!
+//! The generated program never jumps or moves `r_d`, so `r_d` always equals
+//! `r_c` and each `*` or `p` operates on its own encrypted opcode, a value
+//! that depends only on the instruction's position. That makes the effect
+//! of every instruction on `r_a` known in advance, and a breadth-first
+//! search over `*`, `p` and the no-op finds the shortest stretch of code
+//! that leaves the next character in the low byte of `r_a`, ready for `<`.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::error;
+use core::fmt;
+
+use crate::{crazy_op, encode, tri_rotate};
+
+/// The longest string `generate_printer` accepts.
+pub const MAX_LEN: usize = 256;
+
+/// How many instructions the search tries before giving up on a character.
+const MAX_DEPTH: usize = 128;
+
+////////////////////////////////////////////////////////////////////////////////
+// GenError
+////////////////////////////////////////////////////////////////////////////////
+
+/// Why `generate_printer` could not produce a program.
+#[derive(Debug, PartialEq, Eq)]
+pub enum GenError {
+    /// The character at the given index isn't ASCII.
+    NotAscii(char, usize),
+    /// The string is longer than `MAX_LEN` bytes.
+    TooLong,
+    /// No sequence of at most `MAX_DEPTH` instructions produces the
+    /// character at the given index.
+    Unreachable(char, usize),
+}
+
+impl fmt::Display for GenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GenError::NotAscii(c, index) =>
+                write!(f, "Character {:?} at index {} is not ASCII.", c, index),
+            GenError::TooLong => write!(f, "String is longer than {} bytes.", MAX_LEN),
+            GenError::Unreachable(c, index) =>
+                write!(f, "Could not reach character {:?} at index {}.", c, index),
+        }
+    }
+}
+
+impl error::Error for GenError {}
+
+////////////////////////////////////////////////////////////////////////////////
+// Generator
+////////////////////////////////////////////////////////////////////////////////
+
+/// Returns a program in normalized form that prints `s` and halts. Load it
+/// with `init_normalized`.
+pub fn generate_printer(s: &str) -> Result<Vec<u8>, GenError> {
+    if s.len() > MAX_LEN {
+        return Err(GenError::TooLong);
+    }
+
+    let mut program = Vec::new();
+    let mut r_a = 0;
+
+    for (index, c) in s.chars().enumerate() {
+        if !c.is_ascii() {
+            return Err(GenError::NotAscii(c, index));
+        }
+
+        let ops = search(r_a, program.len(), c as u8)
+            .ok_or(GenError::Unreachable(c, index))?;
+
+        for op in ops {
+            r_a = apply(op, r_a, program.len());
+            program.push(op as u8);
+        }
+
+        program.push(b'<');
+    }
+
+    // `init` wants at least two instructions
+    if program.is_empty() {
+        program.push(b'o');
+    }
+
+    program.push(b'v');
+    Ok(program)
+}
+
+/// What executing `op` at `addr` does to `r_a`.
+fn apply(op: char, r_a: usize, addr: usize) -> usize {
+    match op {
+        '*' => tri_rotate(encode('*', addr).unwrap() as usize),
+        'p' => crazy_op(r_a, encode('p', addr).unwrap() as usize),
+        _ => r_a,
+    }
+}
+
+/// Finds the shortest list of instructions, starting at `addr` with `r_a`,
+/// after which the low byte of `r_a` is `target`.
+fn search(r_a: usize, addr: usize, target: u8) -> Option<Vec<char>> {
+    // each layer maps the values `r_a` can have after that many
+    // instructions to the value before and the instruction that led there
+    let mut layers: Vec<BTreeMap<usize, (usize, char)>> = Vec::new();
+    let mut frontier = alloc::vec![r_a];
+
+    for depth in 0..=MAX_DEPTH {
+        if let Some(&found) = frontier.iter().find(|&&v| v % 256 == target as usize) {
+            let mut ops = Vec::with_capacity(depth);
+            let mut value = found;
+
+            for layer in layers.iter().rev() {
+                let (prev, op) = layer[&value];
+                ops.push(op);
+                value = prev;
+            }
+
+            ops.reverse();
+            return Some(ops);
+        }
+
+        let mut layer = BTreeMap::new();
+
+        for &value in &frontier {
+            for op in ['o', '*', 'p'] {
+                layer.entry(apply(op, value, addr + depth)).or_insert((value, op));
+            }
+        }
+
+        frontier = layer.keys().copied().collect();
+        layers.push(layer);
+    }
+
+    None
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Tests
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Halt, InterpreterBuilder};
+
+    fn run(program: &[u8]) -> Vec<u8> {
+        let mut interpreter = InterpreterBuilder::new()
+            .max_steps(1_000_000)
+            .build_normalized(program)
+            .unwrap();
+        let mut output = Vec::new();
+
+        let exit = interpreter.execute(&mut std::io::empty(), &mut output).unwrap();
+        assert_eq!(exit.halt, Halt::Stopped);
+        output
+    }
+
+    #[test]
+    fn generate_printer_test() {
+        for s in ["", "A", "Hello, World!", "\0\n\x7f~ "] {
+            let program = generate_printer(s).unwrap();
+            assert_eq!(run(&program), s.as_bytes(), "{:?}", s);
+        }
+
+        let every: String = (0..128u8).map(|b| b as char).collect();
+        assert_eq!(run(&generate_printer(&every).unwrap()), every.as_bytes());
+    }
+
+    #[test]
+    fn generate_printer_error_test() {
+        assert_eq!(generate_printer("caf\u{e9}"), Err(GenError::NotAscii('\u{e9}', 3)));
+        assert_eq!(generate_printer(&"a".repeat(MAX_LEN + 1)), Err(GenError::TooLong));
+    }
+}
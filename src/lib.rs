@@ -0,0 +1,3213 @@
+// MIT License
+//
+// Copyright (C) 2015-2018 Steve Sprang
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! This is an implementation of the Malbolge interpreter in Rust.
+//! It's basically a translation of the original C version found here:
+//! http://www.lscheffer.com/malbolge_interp.html
+//!
+//! For more information about Malbolge:
+//!     http://en.wikipedia.org/wiki/Malbolge
+//!     http://www.lscheffer.com/malbolge_spec.html
+//!
+//! Without the default `std` feature the crate is `no_std`, needing only
+//! `alloc`. The loader, `Interpreter::step` and the rest of the machine
+//! still work; anything that reads or writes through `std::io` doesn't.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod asm;
+#[cfg(feature = "async")]
+pub mod asynchronous;
+mod bitset;
+pub mod generate;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod malbolge20;
+pub mod minimize;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use bitset::BitSet;
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeSet;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::error;
+use core::fmt;
+use core::ops::Range;
+use core::str::FromStr;
+use core::time::Duration;
+
+#[cfg(feature = "std")]
+use std::cell::RefCell;
+#[cfg(feature = "std")]
+use std::collections::HashSet;
+#[cfg(feature = "std")]
+use std::io::{self, BufRead, BufReader, Read, Write};
+#[cfg(feature = "std")]
+use std::path::Path;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+/// Decodes a cell: the instruction at `addr` holding `value` is
+/// `XLAT1[(value - 33 + addr) % 94]`.
+pub const XLAT1: &[u8; 94] = b"+b(29e*j1VMEKLyC})8&m#~W>qxdRp0wkrUo[D7,XTcA\"lI\
+                        .v%{gJh4G\\-=O@5`_3i<?Z';FNQuY]szf$!BS/|t:Pn6^Ha";
+
+/// Re-encrypts a cell after it runs: `value` becomes `XLAT2[value - 33]`.
+pub const XLAT2: &[u8; 94] = b"5z]&gqtyfr$(we4{WP)H-Zn,[%\\3dL+Q;>U!pJS72FhOA1C\
+                        B6v^=I_0/8|jsb9m<.TVac`uY*MK'X~xDl}REokN:#?G\"i@";
+
+// DECODE[addr % 94][value - 33] == XLAT1[(value - 33 + addr) % 94]
+static DECODE: [[u8; 94]; 94] = decode_table();
+
+pub const MAX_MEMORY: usize = 59049; // == 3^10
+
+/// What the `/` instruction reads at the end of input unless configured
+/// otherwise. This is the largest value that fits in a cell, as in the C
+/// reference interpreter; some other interpreters leave `r_a` unchanged
+/// or use 0 instead.
+pub const EOF_VALUE: usize = MAX_MEMORY - 1;
+
+/// The instructions a program may contain, written in normalized form.
+pub const OPCODES: &str = "ji*p</vo";
+
+// the most register states that loop detection remembers at once
+#[cfg(feature = "std")]
+const LOOP_HISTORY: usize = 1 << 16;
+
+/// How many instructions `execute` runs between looks at the clock when
+/// `Config::max_duration` is set.
+#[cfg(feature = "std")]
+const CLOCK_INTERVAL: u64 = 1 << 12;
+
+// every value fits in 10 trits, so a u16 is enough
+pub type Memory = [u16; MAX_MEMORY];
+
+////////////////////////////////////////////////////////////////////////////////
+// InitError
+////////////////////////////////////////////////////////////////////////////////
+
+/// The reasons a source program can fail to load.
+#[derive(Debug)]
+pub enum InitError {
+    /// A byte that doesn't decode to a valid instruction.
+    InvalidChar {
+        c: char,
+        /// The byte's offset in the source.
+        offset: usize,
+        /// The instruction the byte would have been, counting from zero.
+        /// Skipped whitespace makes this smaller than `offset`.
+        index: usize,
+        /// Where the byte is in the source, counting from one. Columns
+        /// count bytes.
+        line: usize,
+        column: usize,
+    },
+    /// The program has no instructions at all: the source is empty or
+    /// nothing but whitespace.
+    SourceEmpty,
+    /// The program has only one instruction.
+    SourceTooShort,
+    /// The program doesn't fit in memory.
+    SourceTooLong,
+    /// `MemoryFill::Constant` holds a value that isn't less than
+    /// `MAX_MEMORY`.
+    InvalidFill(u16),
+}
+
+use InitError::*;
+
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InvalidChar { c, offset, index, line, column } =>
+                write!(f, "Invalid character in source program: '{}' \
+                           at location: {:#X} (line {}, column {}, instruction {})",
+                       c, offset, line, column, index),
+            SourceEmpty => write!(f, "Source program is empty."),
+            SourceTooShort => write!(f, "Source program is too short."),
+            SourceTooLong => write!(f, "Source program is too long."),
+            InvalidFill(value) => write!(f, "Invalid memory fill value: {}", value),
+        }
+    }
+}
+
+impl error::Error for InitError {}
+
+/// Reports the byte at `offset` in `input`, which would have been
+/// instruction `index`.
+pub(crate) fn invalid_char(input: &[u8], offset: usize, index: usize) -> InitError {
+    let before = &input[..offset];
+    let line = before.iter().filter(|&&b| b == b'\n').count() + 1;
+    let column = offset - before.iter().rposition(|&b| b == b'\n').map_or(0, |n| n + 1) + 1;
+
+    InvalidChar { c: input[offset] as char, offset, index, line, column }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Interpreter
+////////////////////////////////////////////////////////////////////////////////
+
+/// The outcome of executing a single instruction with `Interpreter::step`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepResult {
+    /// The instruction had no externally visible effect.
+    Continue,
+    /// The `<` instruction wrote a byte.
+    Output(u8),
+    /// The `/` instruction wants a byte. The machine does not advance until
+    /// the caller supplies one with `Interpreter::input`.
+    NeedsInput,
+    /// The machine has stopped and will not advance any further, except
+    /// past a `v` with `HaltMode::Resumable`.
+    Halted(Halt),
+    /// A `*` or `p` changed the value of a cell added with
+    /// `Interpreter::watch`. Otherwise this is the same as `Continue`. If
+    /// the instruction wrote to itself, `new` is the value it was then
+    /// re-encrypted to.
+    MemoryChanged { addr: usize, old: usize, new: usize },
+}
+
+use StepResult::*;
+
+/// Why the machine stopped running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Halt {
+    /// The program executed the `v` instruction.
+    Stopped,
+    /// `r_c` reached a cell that does not hold an instruction, its value
+    /// being either below 33 or above 126.
+    FellThrough { addr: usize, value: usize },
+    /// The program ran for `Config::max_steps` instructions.
+    StepLimitReached,
+    /// The program tried to output more than `Config::max_output` bytes.
+    OutputLimitReached,
+    /// The program ran for longer than `Config::max_duration`.
+    Timeout,
+    /// `r_c` reached an address registered with `add_breakpoint`.
+    Breakpoint(usize),
+    /// The registers repeated an earlier state without any input or output
+    /// in between. See `Config::detect_loops`.
+    LoopDetected,
+    /// The input had no byte ready yet, which it signals with
+    /// `io::ErrorKind::WouldBlock`, and `Config::nonblocking_input` is set.
+    /// The `/` is still pending: call `execute` again once there is input,
+    /// or supply a byte with `Interpreter::input`.
+    InputPending,
+}
+
+impl fmt::Display for Halt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Halt::Stopped => write!(f, "Program halted."),
+            Halt::FellThrough { addr, value } => {
+                let why = if value < 33 { "below 33" } else { "above 126" };
+                write!(f, "Program ran into a non-instruction at address {}: \
+                           {} is {}.", addr, value, why)
+            }
+            Halt::StepLimitReached => write!(f, "Step limit reached."),
+            Halt::OutputLimitReached => write!(f, "Output limit reached."),
+            Halt::Timeout => write!(f, "Time limit reached."),
+            Halt::Breakpoint(addr) => write!(f, "Breakpoint at {}.", addr),
+            Halt::LoopDetected => write!(f, "Infinite loop detected."),
+            Halt::InputPending => write!(f, "Waiting for input."),
+        }
+    }
+}
+
+/// The state of the machine when `Interpreter::execute` returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Exit {
+    pub halt: Halt,
+    pub r_a: usize,
+    pub r_c: usize,
+    pub r_d: usize,
+}
+
+/// What `Interpreter::run_with` reports to its handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// The `<` instruction wrote a byte.
+    Output(u8),
+    /// The `/` instruction wants a byte, which the handler returns. `None`
+    /// means EOF.
+    NeedsInput,
+    /// The machine has stopped. This is always the last event.
+    Halted(Exit),
+}
+
+/// How `Interpreter::execute` writes the bytes that `<` emits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputMode {
+    /// Write each byte as is, like the C reference interpreter.
+    #[default]
+    Raw,
+    /// Write each byte as the UTF-8 encoding of the character with that
+    /// code point, so bytes above 127 become two bytes.
+    Utf8Lossy,
+}
+
+/// When `Interpreter::execute` flushes its output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FlushPolicy {
+    /// Leave it to the writer. `run` still flushes once the program halts.
+    #[default]
+    Never,
+    /// Before each `/` reads, so that a prompt shows up even without a
+    /// newline.
+    BeforeInput,
+    /// After each byte `<` writes, and before each `/` reads.
+    EveryByte,
+}
+
+/// What the `v` instruction does to the machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HaltMode {
+    /// Stop for good: every later `step` halts again at the same `v`.
+    #[default]
+    Terminate,
+    /// Stop with the registers still on the `v`, as with `Terminate`, but
+    /// let the next `step` or `execute` carry on past it.
+    Resumable,
+}
+
+/// How the loader fills the memory past the end of the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MemoryFill {
+    /// Each cell is `crazy_op` of the two before it, as the specification
+    /// says.
+    #[default]
+    CrazyOp,
+    /// Every cell holds this value, which must be less than `MAX_MEMORY`
+    /// or loading fails with `InitError::InvalidFill`.
+    /// This is not Malbolge: it is for finding out which behaviors of a
+    /// program depend on the fill.
+    Constant(u16),
+}
+
+/// Decides what the `/` instruction stores in `r_a` at the end of input,
+/// for reproducing interpreters that disagree about it. See
+/// `Config::input_policy`.
+pub trait InputPolicy: fmt::Debug + Send + Sync {
+    /// Returns the new value of `r_a` given the current one. It must be
+    /// less than `MAX_MEMORY`.
+    fn on_eof(&self, r_a: usize) -> usize;
+}
+
+/// Leaves `r_a` as it was at the end of input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Unchanged;
+
+impl InputPolicy for Unchanged {
+    fn on_eof(&self, r_a: usize) -> usize {
+        r_a
+    }
+}
+
+/// Options that control how `Interpreter::execute` runs a program.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Stop after executing this many instructions. `None` means no limit.
+    pub max_steps: Option<u64>,
+    /// Stop at a `<` instruction that would output more than this many
+    /// bytes, without executing it. `None` means no limit.
+    pub max_output: Option<usize>,
+    /// Stop once the program has run for this long. The clock is only
+    /// checked every few thousand instructions, so the program may run a
+    /// little longer. `None` means no limit.
+    pub max_duration: Option<Duration>,
+    /// Log each instruction before executing it, to stderr or to the writer
+    /// given to `InterpreterBuilder::trace_output`. Trace lines never go to
+    /// the program's own output.
+    pub trace: bool,
+    /// Color trace lines with ANSI escapes: the opcode, any register that
+    /// changed since the last line, and `r_a` when `<` is about to write
+    /// it.
+    pub trace_color: bool,
+    /// Pause this long before each instruction, to watch a program run.
+    /// `None` or zero means no pause.
+    pub step_delay: Option<Duration>,
+    /// The value the `/` instruction stores in `r_a` at the end of input.
+    /// It must be less than `MAX_MEMORY`.
+    pub eof_value: usize,
+    /// Decides what `/` stores in `r_a` at the end of input instead of
+    /// `eof_value`, if set.
+    pub input_policy: Option<Arc<dyn InputPolicy>>,
+    /// Stop if `r_a`, `r_c` and `r_d` return to values they held earlier
+    /// without any input or output in between. Memory is not compared, so
+    /// this is only a heuristic: self-modifying code may still go on to do
+    /// something new.
+    pub detect_loops: bool,
+    /// How bytes written by `<` reach the output.
+    pub output_mode: OutputMode,
+    /// When to flush the output.
+    pub flush_policy: FlushPolicy,
+    /// Keep this many of the most recent bytes that `execute` wrote, for
+    /// `Interpreter::last_output`. Zero keeps none.
+    pub output_history: usize,
+    /// Record every byte that `execute` writes along with where it came
+    /// from, for `Interpreter::output_log`. The log grows without bound.
+    pub log_output: bool,
+    /// Record each address that an instruction is executed from. See
+    /// `Interpreter::coverage`.
+    pub coverage: bool,
+    /// Record each `*` or `p` that writes to a cell an instruction has
+    /// already been executed from. This records coverage too. See
+    /// `Interpreter::self_modifications`.
+    pub watch_writes: bool,
+    /// Whether the machine can go on past a `v`.
+    pub halt_mode: HaltMode,
+    /// Stop with `Halt::InputPending` when a read fails with
+    /// `io::ErrorKind::WouldBlock`, instead of treating it as an error.
+    /// Only a read of zero bytes means the end of input.
+    pub nonblocking_input: bool,
+    /// How memory past the program is filled when it is loaded. Anything
+    /// but the default `MemoryFill::CrazyOp` is non-standard. Memory
+    /// images are used as they are.
+    pub memory_fill: MemoryFill,
+    /// Load programs with `init_lenient`, skipping invalid characters
+    /// instead of failing. With the `tracing` feature each one is logged as
+    /// a warning.
+    pub lenient: bool,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            max_steps: None,
+            max_output: None,
+            max_duration: None,
+            trace: false,
+            trace_color: false,
+            step_delay: None,
+            eof_value: EOF_VALUE,
+            input_policy: None,
+            detect_loops: false,
+            output_mode: OutputMode::Raw,
+            flush_policy: FlushPolicy::Never,
+            output_history: 0,
+            log_output: false,
+            coverage: false,
+            watch_writes: false,
+            halt_mode: HaltMode::Terminate,
+            nonblocking_input: false,
+            memory_fill: MemoryFill::CrazyOp,
+            lenient: false,
+        }
+    }
+}
+
+/// A copy of the machine's registers and memory, taken with
+/// `Interpreter::save_state`. With the `serde` feature, it can be
+/// serialized to save a long-running program and resume it later.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct State {
+    pub r_a: usize,
+    pub r_c: usize,
+    pub r_d: usize,
+    pub mem: Box<Memory>,
+    /// Whether a `v` has halted the machine with `HaltMode::Resumable`, so
+    /// that the next step carries on past it.
+    pub stopped: bool,
+    /// What `instruction_count` and `output_count` return.
+    pub instruction_count: u64,
+    pub output_count: u64,
+}
+
+/// A Malbolge virtual machine: the memory image and the three registers.
+///
+/// Each instance owns its memory, about 115 KiB (`MAX_MEMORY` cells of two
+/// bytes each), plus a copy of the memory it started with for `reset`, and
+/// nothing else is shared, so instances can be moved to other threads and
+/// run in parallel.
+pub struct Interpreter {
+    mem: Box<Memory>,
+    initial: Box<Memory>,
+    // the number of cells `init` loaded the program into
+    len: usize,
+    r_a: usize,
+    r_c: usize,
+    r_d: usize,
+    config: Config,
+    breakpoints: BTreeSet<usize>,
+    watches: BTreeSet<usize>,
+    instructions_executed: u64,
+    bytes_output: u64,
+    coverage: BitSet,
+    // set while a resumable `v` has halted the machine
+    stopped: bool,
+    // up to twice `Config::output_history` bytes, the last of them wanted
+    history: Vec<u8>,
+    // (instruction index, address, byte) for `log_output`
+    output_log: Vec<(u64, usize, u8)>,
+    // (written address, writing instruction) pairs for `watch_writes`
+    modifications: Vec<(usize, usize)>,
+    #[cfg(feature = "std")]
+    input: Option<Box<dyn Read + Send>>,
+    #[cfg(feature = "std")]
+    output: Option<Box<dyn Write + Send>>,
+    #[cfg(feature = "std")]
+    trace_output: Option<Box<dyn Write + Send>>,
+}
+
+impl Interpreter {
+    /// Loads `source` into a fresh machine with all registers set to zero.
+    pub fn new(source: &[u8]) -> Result<Interpreter, InitError> {
+        Interpreter::with_config(source, Config::default())
+    }
+
+    /// Like `new`, but runs according to `config`.
+    pub fn with_config(source: &[u8],
+                       config: Config) -> Result<Interpreter, InitError> {
+        let mut mem = Interpreter::alloc();
+        let len = if config.lenient {
+            let (len, _skipped) = init_lenient(source, &mut mem)?;
+
+            #[cfg(feature = "tracing")]
+            for e in _skipped {
+                tracing::warn!("skipped {}", e);
+            }
+
+            len
+        } else {
+            init(source, &mut mem)?
+        };
+
+        Interpreter::from_program(mem, len, config)
+    }
+
+    /// Like `with_config`, but loads a program written in normalized form.
+    pub fn from_normalized(source: &[u8],
+                           config: Config) -> Result<Interpreter, InitError> {
+        let mut mem = Interpreter::alloc();
+        let len = init_normalized(source, &mut mem)?;
+
+        Interpreter::from_program(mem, len, config)
+    }
+
+    /// Starts a machine from a memory image, such as one read by
+    /// `load_image`, with every register set to zero. The whole image counts
+    /// as the program.
+    ///
+    /// Panics if a cell is not less than `MAX_MEMORY`.
+    pub fn from_image(mem: Box<Memory>, config: Config) -> Interpreter {
+        assert!(mem.iter().all(|&cell| (cell as usize) < MAX_MEMORY),
+                "invalid memory image");
+
+        Interpreter::from_memory(mem, MAX_MEMORY, config)
+    }
+
+    fn alloc() -> Box<Memory> {
+        // allocate directly on the heap; the array is too big for the stack
+        vec![0; MAX_MEMORY].try_into().unwrap()
+    }
+
+    /// Applies `Config::memory_fill` to a program the loader has just put
+    /// in `mem`.
+    fn from_program(mut mem: Box<Memory>, len: usize, config: Config) -> Result<Interpreter, InitError> {
+        // the loader has already done the standard fill
+        if config.memory_fill != MemoryFill::CrazyOp {
+            fill_memory(&mut mem, len, config.memory_fill)?;
+        }
+
+        Ok(Interpreter::from_memory(mem, len, config))
+    }
+
+    fn from_memory(mem: Box<Memory>, len: usize, config: Config) -> Interpreter {
+        Interpreter {
+            initial: mem.clone(),
+            mem,
+            len,
+            r_a: 0,
+            r_c: 0,
+            r_d: 0,
+            config,
+            breakpoints: BTreeSet::new(),
+            watches: BTreeSet::new(),
+            instructions_executed: 0,
+            bytes_output: 0,
+            coverage: BitSet::new(),
+            stopped: false,
+            history: Vec::new(),
+            output_log: Vec::new(),
+            modifications: Vec::new(),
+            #[cfg(feature = "std")]
+            input: None,
+            #[cfg(feature = "std")]
+            output: None,
+            #[cfg(feature = "std")]
+            trace_output: None,
+        }
+    }
+
+    /// Runs the program until it halts. The `/` instruction reads from
+    /// `input` and the `<` instruction writes to `output`.
+    ///
+    /// The instruction at `r_c` when `execute` is called always runs, even
+    /// if it has a breakpoint, so that execution can resume after stopping
+    /// at one.
+    ///
+    /// An error reading `input` other than `ErrorKind::Interrupted`, which
+    /// is retried, is returned with the machine still at the `/` and `r_a`
+    /// unchanged, so calling `execute` again tries the read again.
+    #[cfg(feature = "std")]
+    pub fn execute(&mut self,
+                   input: &mut dyn Read,
+                   output: &mut dyn Write) -> io::Result<Exit> {
+        // groups the events of one run
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("execute", r_c = self.r_c).entered();
+
+        let mut steps = 0;
+        let mut written = 0;
+        let mut history = HashSet::new();
+        let mut traced = None;
+        let deadline = self.config.max_duration.map(|max| Instant::now() + max);
+        let delay = self.config.step_delay.filter(|delay| !delay.is_zero());
+
+        loop {
+            if self.config.max_steps.is_some_and(|max| steps >= max) {
+                return Ok(self.exit(Halt::StepLimitReached));
+            }
+
+            if let Some(deadline) = deadline {
+                if steps % CLOCK_INTERVAL == 0 && Instant::now() >= deadline {
+                    return Ok(self.exit(Halt::Timeout));
+                }
+            }
+
+            if self.config.detect_loops {
+                // start over rather than grow without bound
+                if history.len() == LOOP_HISTORY {
+                    history.clear();
+                }
+
+                if !history.insert(self.registers()) {
+                    return Ok(self.exit(Halt::LoopDetected));
+                }
+            }
+
+            if steps > 0 && self.breakpoints.contains(&self.r_c) {
+                return Ok(self.exit(Halt::Breakpoint(self.r_c)));
+            }
+
+            if self.config.max_output.is_some_and(|max| written >= max) && self.at_output() {
+                return Ok(self.exit(Halt::OutputLimitReached));
+            }
+
+            if self.config.trace && is_printable(self.mem[self.r_c] as usize) {
+                let line = self.trace_line(traced);
+                self.trace(&line)?;
+                traced = Some((self.r_a, self.r_d));
+            }
+
+            if let Some(delay) = delay {
+                // show the output so far while paused
+                output.flush()?;
+                std::thread::sleep(delay);
+            }
+
+            steps += 1;
+            let modified = self.modifications.len();
+            let result = self.step();
+
+            if self.config.trace && self.modifications.len() > modified {
+                let (addr, _) = self.modifications[modified];
+                self.trace(&format!("       overwrote executed instruction at {}", addr))?;
+            }
+
+            match result {
+                Continue | MemoryChanged { .. } => {}
+                Output(b) => {
+                    written += 1;
+                    self.bytes_output += 1;
+
+                    match self.config.output_mode {
+                        OutputMode::Raw => output.write_all(&[b])?,
+                        OutputMode::Utf8Lossy => write!(output, "{}", b as char)?,
+                    }
+
+                    if self.config.flush_policy == FlushPolicy::EveryByte {
+                        output.flush()?;
+                    }
+
+                    self.remember_output(b);
+
+                    history.clear();
+                }
+                NeedsInput => {
+                    history.clear();
+
+                    if self.config.flush_policy != FlushPolicy::Never {
+                        output.flush()?;
+                    }
+
+                    match read_byte(input) {
+                        Ok(b) => self.input(b),
+                        // no byte yet, which isn't the end of input
+                        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock
+                            && self.config.nonblocking_input => {
+                            return Ok(self.exit(Halt::InputPending));
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+                Halted(halt) => return Ok(self.exit(halt)),
+            }
+        }
+    }
+
+    /// Like `execute`, but reads from and writes to the input and output
+    /// given to `InterpreterBuilder`, or stdin and stdout if there were
+    /// none. The output is flushed once the program halts.
+    #[cfg(feature = "std")]
+    pub fn run(&mut self) -> io::Result<Exit> {
+        let mut input = self.input.take().unwrap_or_else(|| Box::new(io::stdin()));
+        let mut output = self.output.take().unwrap_or_else(|| Box::new(io::stdout()));
+
+        let result = self.execute(&mut input, &mut output)
+            .and_then(|exit| output.flush().map(|_| exit));
+
+        self.input = Some(input);
+        self.output = Some(output);
+        result
+    }
+
+    /// Like `execute`, but hands each output byte, input request and the
+    /// final halt to `handler` as soon as it happens. The handler's return
+    /// value is only used for `Event::NeedsInput`. The handler itself can't
+    /// fail, but writing a trace line can, and that error is returned
+    /// without an `Event::Halted`.
+    #[cfg(feature = "std")]
+    pub fn run_with<F>(&mut self, handler: F) -> io::Result<Exit>
+        where F: FnMut(Event) -> Option<u8>
+    {
+        let handler = RefCell::new(handler);
+
+        let exit = self.execute(&mut EventInput(&handler), &mut EventOutput(&handler))?;
+
+        (handler.borrow_mut())(Event::Halted(exit));
+        Ok(exit)
+    }
+
+    /// Executes exactly one instruction.
+    pub fn step(&mut self) -> StepResult {
+        // every register that is used as an address comes from memory or
+        // from `next_addr`, and memory only holds values below MAX_MEMORY
+        debug_assert!(self.r_c < MAX_MEMORY && self.r_d < MAX_MEMORY);
+
+        let value = self.mem[self.r_c] as usize;
+
+        if !is_printable(value) {
+            return Halted(Halt::FellThrough { addr: self.r_c, value });
+        }
+
+        let op = self.op();
+
+        if self.config.coverage || self.config.watch_writes {
+            self.coverage.insert(self.r_c);
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(op = %op, r_a = self.r_a, r_c = self.r_c, r_d = self.r_d, "step");
+
+        // the only cell that `*` and `p` write to
+        let (addr, old) = (self.r_d, self.mem[self.r_d] as usize);
+        let mem = &mut self.mem;
+
+        let result = match op {
+            'j' => {
+                self.r_d = mem[self.r_d] as usize;
+                Continue
+            }
+            'i' => {
+                self.r_c = mem[self.r_d] as usize;
+                Continue
+            }
+            '*' => {
+                self.r_a = tri_rotate(mem[self.r_d] as usize);
+                mem[self.r_d] = self.r_a as u16;
+                Continue
+            }
+            'p' => {
+                self.r_a = crazy_op(self.r_a, mem[self.r_d] as usize);
+                mem[self.r_d] = self.r_a as u16;
+                Continue
+            }
+            // only the low byte is output, as in C
+            '<' => Output(self.r_a as u8),
+            '/' => return NeedsInput,
+            // the second step at a resumable `v` goes past it
+            'v' if self.stopped => {
+                self.stopped = false;
+                Continue
+            }
+            'v' => {
+                self.stopped = self.config.halt_mode == HaltMode::Resumable;
+                return Halted(Halt::Stopped);
+            }
+            _ => Continue, // no op
+        };
+
+        #[cfg(feature = "tracing")]
+        if let Output(b) = result {
+            tracing::debug!(byte = b, "output");
+        }
+
+        if self.config.watch_writes && matches!(op, '*' | 'p') && self.coverage.contains(self.r_d) {
+            self.modifications.push((self.r_d, self.r_c));
+
+            #[cfg(feature = "tracing")]
+            tracing::debug!(addr = self.r_d, r_c = self.r_c, "self-modification");
+        }
+
+        self.advance();
+
+        let new = self.mem[addr] as usize;
+        if matches!(op, '*' | 'p') && new != old && self.watches.contains(&addr) {
+            return MemoryChanged { addr, old, new };
+        }
+
+        result
+    }
+
+    /// Completes a pending `/` instruction. `None` signals end of input,
+    /// which stores `Config::eof_value` in `r_a` or whatever
+    /// `Config::input_policy` decides.
+    pub fn input(&mut self, byte: Option<u8>) {
+        debug_assert_eq!(self.op(), '/');
+
+        self.r_a = match byte {
+            Some(b) => b as usize,
+            None => match self.config.input_policy {
+                Some(ref policy) => policy.on_eof(self.r_a),
+                None => self.config.eof_value,
+            },
+        };
+
+        debug_assert!(self.r_a < MAX_MEMORY);
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(byte = ?byte, "input");
+
+        self.advance();
+    }
+
+    /// Steps until something other than `StepResult::Continue` happens, and
+    /// returns it. Like `execute`, it stops at a breakpoint unless that is
+    /// where it started, and after `Config::max_steps` instructions, with
+    /// `Halt::Breakpoint` or `Halt::StepLimitReached`.
+    pub fn run_until_event(&mut self) -> StepResult {
+        let mut steps = 0;
+
+        loop {
+            if self.config.max_steps.is_some_and(|max| steps >= max) {
+                return Halted(Halt::StepLimitReached);
+            }
+
+            if steps > 0 && self.breakpoints.contains(&self.r_c) {
+                return Halted(Halt::Breakpoint(self.r_c));
+            }
+
+            steps += 1;
+
+            match self.step() {
+                Continue => {}
+                result => return result,
+            }
+        }
+    }
+
+    /// Returns the registers as `(r_a, r_c, r_d)`.
+    pub fn registers(&self) -> (usize, usize, usize) {
+        (self.r_a, self.r_c, self.r_d)
+    }
+
+    /// Returns the value of the memory cell at `addr`.
+    pub fn mem(&self, addr: usize) -> usize {
+        self.mem[addr] as usize
+    }
+
+    /// Borrows the whole of memory as it is now, for reading many cells
+    /// without the copy that `save_state` makes.
+    pub fn mem_view(&self) -> &[u16] {
+        &self.mem[..]
+    }
+
+    /// Copies the registers and memory, along with whether the machine is
+    /// halted and how much it has done.
+    pub fn save_state(&self) -> State {
+        State {
+            r_a: self.r_a,
+            r_c: self.r_c,
+            r_d: self.r_d,
+            mem: self.mem.clone(),
+            stopped: self.stopped,
+            instruction_count: self.instructions_executed,
+            output_count: self.bytes_output,
+        }
+    }
+
+    /// Puts the machine back into a state saved by `save_state`.
+    ///
+    /// Panics if a register or memory cell in `state` is not less than
+    /// `MAX_MEMORY`, which `save_state` never produces.
+    pub fn load_state(&mut self, state: &State) {
+        assert!(state.r_c < MAX_MEMORY && state.r_d < MAX_MEMORY
+                && state.mem.iter().all(|&cell| (cell as usize) < MAX_MEMORY),
+                "invalid state");
+
+        self.r_a = state.r_a;
+        self.r_c = state.r_c;
+        self.r_d = state.r_d;
+        self.mem.copy_from_slice(&state.mem[..]);
+        self.stopped = state.stopped;
+        self.instructions_executed = state.instruction_count;
+        self.bytes_output = state.output_count;
+    }
+
+    /// Puts the machine back the way it was loaded: memory as it was right
+    /// after `init`, every register zero, and no instructions executed or
+    /// covered. The configuration, breakpoints, watches and I/O are kept.
+    pub fn reset(&mut self) {
+        self.mem.copy_from_slice(&self.initial[..]);
+        self.r_a = 0;
+        self.r_c = 0;
+        self.r_d = 0;
+        self.instructions_executed = 0;
+        self.bytes_output = 0;
+        self.coverage.clear();
+        self.stopped = false;
+        self.history.clear();
+        self.output_log.clear();
+        self.modifications.clear();
+    }
+
+    /// Returns the number of instructions executed so far, whether by
+    /// `execute` or `step`.
+    pub fn instruction_count(&self) -> u64 {
+        self.instructions_executed
+    }
+
+    /// Sets the instruction count back to zero.
+    pub fn reset_instruction_count(&mut self) {
+        self.instructions_executed = 0;
+    }
+
+    /// Returns the number of bytes `execute` has written so far.
+    pub fn output_count(&self) -> u64 {
+        self.bytes_output
+    }
+
+    /// Returns the last bytes `execute` wrote, oldest first: at most
+    /// `Config::output_history` of them.
+    pub fn last_output(&self) -> &[u8] {
+        let keep = self.config.output_history.min(self.history.len());
+        &self.history[self.history.len() - keep..]
+    }
+
+    /// Returns every byte `execute` has written with `Config::log_output`
+    /// set, in order, as `(instruction, r_c, byte)`: how many instructions
+    /// had been executed before the `<` that wrote it, and its address.
+    pub fn output_log(&self) -> &[(u64, usize, u8)] {
+        &self.output_log
+    }
+
+    /// Returns the addresses that instructions have been executed from,
+    /// which is only recorded if `Config::coverage` or
+    /// `Config::watch_writes` is set.
+    pub fn coverage(&self) -> &BitSet {
+        &self.coverage
+    }
+
+    /// Returns each write by `*` or `p` to a cell that an instruction had
+    /// already been executed from, as the written address and the address
+    /// of the instruction that wrote it, in order. This is only recorded if
+    /// `Config::watch_writes` is set.
+    pub fn self_modifications(&self) -> &[(usize, usize)] {
+        &self.modifications
+    }
+
+    /// Makes `execute` stop when `r_c` reaches `addr`.
+    pub fn add_breakpoint(&mut self, addr: usize) {
+        self.breakpoints.insert(addr);
+    }
+
+    /// Removes a breakpoint added with `add_breakpoint`.
+    pub fn remove_breakpoint(&mut self, addr: usize) {
+        self.breakpoints.remove(&addr);
+    }
+
+    /// Removes every breakpoint.
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// Makes `step` return `StepResult::MemoryChanged` when an instruction
+    /// changes the value of the cell at `addr`.
+    pub fn watch(&mut self, addr: usize) {
+        self.watches.insert(addr);
+    }
+
+    /// Removes a watch added with `watch`.
+    pub fn unwatch(&mut self, addr: usize) {
+        self.watches.remove(&addr);
+    }
+
+    /// Decodes part of memory as described by `disassemble`.
+    pub fn disassemble(&self, start: usize, count: usize) -> Vec<(usize, char)> {
+        disassemble(&self.mem, start, count)
+    }
+
+    /// Decodes the program one cell at a time as `disassemble` does, from
+    /// address 0 up to the length it was loaded with.
+    ///
+    /// ```
+    /// use malbolge::Interpreter;
+    ///
+    /// let source = include_bytes!("../tests/fixtures/hello-world.mb");
+    /// let interpreter = Interpreter::new(source).unwrap();
+    /// let halts = interpreter.instructions().filter(|&(_, op)| op == 'v').count();
+    ///
+    /// assert_eq!(halts, 2);
+    /// ```
+    pub fn instructions(&self) -> impl Iterator<Item = (usize, char)> + '_ {
+        (0..self.len).map(|addr| (addr, decode_cell(&self.mem, addr)))
+    }
+
+    /// Lists part of memory as described by `listing`.
+    pub fn listing(&self, range: Range<usize>) -> String {
+        listing(&self.mem, range)
+    }
+
+    /// Writes the contents of memory to `out` in the format described by
+    /// `dump_memory`.
+    #[cfg(feature = "std")]
+    pub fn dump_memory(&self, out: &mut dyn Write) -> io::Result<()> {
+        dump_memory(&self.mem, out)
+    }
+
+    /// Adds `b`, just written by the `<` before `r_c`, to the bytes kept
+    /// for `last_output` and `output_log`. Old bytes are dropped in batches
+    /// so that each byte is only moved once.
+    #[cfg(feature = "std")]
+    fn remember_output(&mut self, b: u8) {
+        if self.config.log_output {
+            // `<` never jumps, so it is right before `r_c`
+            let addr = self.r_c.checked_sub(1).unwrap_or(MAX_MEMORY - 1);
+            self.output_log.push((self.instructions_executed - 1, addr, b));
+        }
+
+        let keep = self.config.output_history;
+
+        if keep > 0 {
+            if self.history.len() >= 2 * keep {
+                self.history.drain(..self.history.len() - keep);
+            }
+
+            self.history.push(b);
+        }
+    }
+
+    /// Writes a line to the trace output, or stderr if there is none.
+    #[cfg(feature = "std")]
+    fn trace(&mut self, line: &str) -> io::Result<()> {
+        match self.trace_output {
+            Some(ref mut trace) => writeln!(trace, "{}", line),
+            None => {
+                eprintln!("{}", line);
+                Ok(())
+            }
+        }
+    }
+
+    /// Describes the instruction about to be executed, which must be
+    /// printable. `previous` holds `r_a` and `r_d` from the last line, to
+    /// show which of them changed.
+    #[cfg(feature = "std")]
+    fn trace_line(&self, previous: Option<(usize, usize)>) -> String {
+        let op = self.op();
+        let (r_a, r_d) = (format!("{:>5}", self.r_a), format!("{:>5}", self.r_d));
+
+        if !self.config.trace_color {
+            return format!("{:>5}  {}  r_a = {}  r_d = {}", self.r_c, op, r_a, r_d);
+        }
+
+        // SGR codes for bold cyan, green and yellow
+        let (op_color, output_color, changed_color) = ("1;36", "32", "33");
+        let paint = |text: String, on: bool, color: &str| match on {
+            true => format!("\x1b[{}m{}\x1b[0m", color, text),
+            false => text,
+        };
+
+        let (a_changed, d_changed) = previous
+            .map_or((false, false), |(a, d)| (a != self.r_a, d != self.r_d));
+        let r_a = match op {
+            '<' => paint(r_a, true, output_color),
+            _ => paint(r_a, a_changed, changed_color),
+        };
+
+        format!("{:>5}  {}  r_a = {}  r_d = {}", self.r_c, paint(op.to_string(), true, op_color),
+                r_a, paint(r_d, d_changed, changed_color))
+    }
+
+    #[cfg(feature = "std")]
+    fn exit(&self, halt: Halt) -> Exit {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(halt = %halt, r_a = self.r_a, r_c = self.r_c, r_d = self.r_d, "halt");
+
+        Exit { halt, r_a: self.r_a, r_c: self.r_c, r_d: self.r_d }
+    }
+
+    /// Decodes the instruction at `r_c`, which must be printable.
+    fn op(&self) -> char {
+        debug_assert!(is_printable(self.mem[self.r_c] as usize));
+        decode(self.mem[self.r_c] as usize, self.r_c)
+    }
+
+    /// Whether the next instruction is a `<`.
+    #[cfg(feature = "std")]
+    fn at_output(&self) -> bool {
+        is_printable(self.mem[self.r_c] as usize) && self.op() == '<'
+    }
+
+    /// Re-encrypts the current instruction and moves on to the next one.
+    ///
+    /// After a jump the current cell is the jump target rather than the
+    /// `i`, so that is the cell that gets re-encrypted. This looks like a
+    /// mistake, but it's what the C reference does and what existing
+    /// programs rely on.
+    fn advance(&mut self) {
+        self.instructions_executed += 1;
+
+        // a jump may land on a cell that holds no instruction, which C
+        // would index XLAT2 out of bounds for; leave it alone and let the
+        // next step report it
+        let value = self.mem[self.r_c] as usize;
+
+        if is_printable(value) {
+            self.mem[self.r_c] = XLAT2[value - 33] as u16;
+
+            // otherwise the cell would stop being an instruction
+            debug_assert!(is_printable(self.mem[self.r_c] as usize));
+        }
+
+        self.r_c = next_addr(self.r_c);
+        self.r_d = next_addr(self.r_d);
+    }
+}
+
+/// Copies the registers, memory, configuration, breakpoints, watches and
+/// instruction count, which means copying the whole memory image. The input
+/// and output given to `InterpreterBuilder` can't be copied, so `run` on the
+/// clone uses stdin and stdout.
+impl Clone for Interpreter {
+    fn clone(&self) -> Interpreter {
+        Interpreter {
+            mem: self.mem.clone(),
+            initial: self.initial.clone(),
+            len: self.len,
+            r_a: self.r_a,
+            r_c: self.r_c,
+            r_d: self.r_d,
+            config: self.config.clone(),
+            breakpoints: self.breakpoints.clone(),
+            watches: self.watches.clone(),
+            instructions_executed: self.instructions_executed,
+            bytes_output: self.bytes_output,
+            coverage: self.coverage.clone(),
+            stopped: self.stopped,
+            history: self.history.clone(),
+            output_log: self.output_log.clone(),
+            modifications: self.modifications.clone(),
+            #[cfg(feature = "std")]
+            input: None,
+            #[cfg(feature = "std")]
+            output: None,
+            #[cfg(feature = "std")]
+            trace_output: None,
+        }
+    }
+}
+
+/// Loads a program as `Interpreter::new` does.
+impl TryFrom<&[u8]> for Interpreter {
+    type Error = InitError;
+
+    fn try_from(source: &[u8]) -> Result<Interpreter, InitError> {
+        Interpreter::new(source)
+    }
+}
+
+/// Loads a program as `Interpreter::new` does.
+///
+/// ```
+/// use malbolge::Interpreter;
+///
+/// let source = include_str!("../tests/fixtures/hello-world.mb");
+/// let interpreter: Interpreter = source.parse().unwrap();
+/// assert_eq!(interpreter.registers(), (0, 0, 0));
+/// ```
+impl FromStr for Interpreter {
+    type Err = InitError;
+
+    fn from_str(source: &str) -> Result<Interpreter, InitError> {
+        Interpreter::new(source.as_bytes())
+    }
+}
+
+/// Turns `execute`'s reads into `Event::NeedsInput`.
+#[cfg(feature = "std")]
+struct EventInput<'a, F>(&'a RefCell<F>);
+
+#[cfg(feature = "std")]
+impl<F: FnMut(Event) -> Option<u8>> Read for EventInput<'_, F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match (self.0.borrow_mut())(Event::NeedsInput) {
+            Some(b) if !buf.is_empty() => {
+                buf[0] = b;
+                Ok(1)
+            }
+            _ => Ok(0),
+        }
+    }
+}
+
+/// Turns `execute`'s writes into `Event::Output`.
+#[cfg(feature = "std")]
+struct EventOutput<'a, F>(&'a RefCell<F>);
+
+#[cfg(feature = "std")]
+impl<F: FnMut(Event) -> Option<u8>> Write for EventOutput<'_, F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut handler = self.0.borrow_mut();
+        for &b in buf {
+            handler(Event::Output(b));
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// InterpreterBuilder
+////////////////////////////////////////////////////////////////////////////////
+
+/// Configures an `Interpreter` one option at a time. Anything left unset
+/// keeps its value from `Config::default()`.
+///
+/// ```
+/// use malbolge::InterpreterBuilder;
+///
+/// let source = include_bytes!("../tests/fixtures/hello-world.mb");
+/// let mut interpreter = InterpreterBuilder::new()
+///     .max_steps(1_000_000)
+///     .input(std::io::empty())
+///     .output(std::io::sink())
+///     .build(source)
+///     .unwrap();
+///
+/// interpreter.run().unwrap();
+/// ```
+#[derive(Default)]
+pub struct InterpreterBuilder {
+    config: Config,
+    breakpoints: Vec<usize>,
+    #[cfg(feature = "std")]
+    input: Option<Box<dyn Read + Send>>,
+    #[cfg(feature = "std")]
+    output: Option<Box<dyn Write + Send>>,
+    #[cfg(feature = "std")]
+    trace_output: Option<Box<dyn Write + Send>>,
+}
+
+impl InterpreterBuilder {
+    pub fn new() -> InterpreterBuilder {
+        InterpreterBuilder::default()
+    }
+
+    /// Sets `Config::max_steps`.
+    pub fn max_steps(mut self, max: u64) -> InterpreterBuilder {
+        self.config.max_steps = Some(max);
+        self
+    }
+
+    /// Sets `Config::max_output`.
+    pub fn max_output(mut self, max: usize) -> InterpreterBuilder {
+        self.config.max_output = Some(max);
+        self
+    }
+
+    /// Sets `Config::max_duration`.
+    pub fn max_duration(mut self, max: Duration) -> InterpreterBuilder {
+        self.config.max_duration = Some(max);
+        self
+    }
+
+    /// Sets `Config::trace`.
+    pub fn trace(mut self, trace: bool) -> InterpreterBuilder {
+        self.config.trace = trace;
+        self
+    }
+
+    /// Sets `Config::trace_color`.
+    pub fn trace_color(mut self, color: bool) -> InterpreterBuilder {
+        self.config.trace_color = color;
+        self
+    }
+
+    /// Sets `Config::step_delay`.
+    pub fn step_delay(mut self, delay: Duration) -> InterpreterBuilder {
+        self.config.step_delay = Some(delay);
+        self
+    }
+
+    /// Sets `Config::eof_value`.
+    pub fn eof_value(mut self, value: usize) -> InterpreterBuilder {
+        self.config.eof_value = value;
+        self
+    }
+
+    /// Sets `Config::input_policy`.
+    pub fn input_policy<P: InputPolicy + 'static>(mut self, policy: P) -> InterpreterBuilder {
+        self.config.input_policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Sets `Config::detect_loops`.
+    pub fn detect_loops(mut self, detect: bool) -> InterpreterBuilder {
+        self.config.detect_loops = detect;
+        self
+    }
+
+    /// Sets `Config::coverage`.
+    pub fn coverage(mut self, coverage: bool) -> InterpreterBuilder {
+        self.config.coverage = coverage;
+        self
+    }
+
+    /// Sets `Config::halt_mode`.
+    pub fn halt_mode(mut self, mode: HaltMode) -> InterpreterBuilder {
+        self.config.halt_mode = mode;
+        self
+    }
+
+    /// Sets `Config::output_history`.
+    pub fn output_history(mut self, len: usize) -> InterpreterBuilder {
+        self.config.output_history = len;
+        self
+    }
+
+    /// Sets `Config::log_output`.
+    pub fn log_output(mut self, log: bool) -> InterpreterBuilder {
+        self.config.log_output = log;
+        self
+    }
+
+    /// Sets `Config::nonblocking_input`.
+    pub fn nonblocking_input(mut self, nonblocking: bool) -> InterpreterBuilder {
+        self.config.nonblocking_input = nonblocking;
+        self
+    }
+
+    /// Sets `Config::watch_writes`.
+    pub fn watch_writes(mut self, watch: bool) -> InterpreterBuilder {
+        self.config.watch_writes = watch;
+        self
+    }
+
+    /// Sets `Config::output_mode`.
+    pub fn output_mode(mut self, mode: OutputMode) -> InterpreterBuilder {
+        self.config.output_mode = mode;
+        self
+    }
+
+    /// Sets `Config::memory_fill`.
+    pub fn memory_fill(mut self, fill: MemoryFill) -> InterpreterBuilder {
+        self.config.memory_fill = fill;
+        self
+    }
+
+    /// Sets `Config::lenient`.
+    pub fn lenient(mut self, lenient: bool) -> InterpreterBuilder {
+        self.config.lenient = lenient;
+        self
+    }
+
+    /// Sets `Config::flush_policy`.
+    pub fn flush_policy(mut self, policy: FlushPolicy) -> InterpreterBuilder {
+        self.config.flush_policy = policy;
+        self
+    }
+
+    /// Adds a breakpoint, as with `Interpreter::add_breakpoint`.
+    pub fn breakpoint(mut self, addr: usize) -> InterpreterBuilder {
+        self.breakpoints.push(addr);
+        self
+    }
+
+    /// Sets where `Interpreter::run` reads input from.
+    #[cfg(feature = "std")]
+    pub fn input<R: Read + Send + 'static>(mut self, input: R) -> InterpreterBuilder {
+        self.input = Some(Box::new(input));
+        self
+    }
+
+    /// Sets where `Interpreter::run` writes output to.
+    #[cfg(feature = "std")]
+    pub fn output<W: Write + Send + 'static>(mut self, output: W) -> InterpreterBuilder {
+        self.output = Some(Box::new(output));
+        self
+    }
+
+    /// Sets where `Config::trace` logs instructions to instead of stderr.
+    #[cfg(feature = "std")]
+    pub fn trace_output<W: Write + Send + 'static>(mut self, trace: W) -> InterpreterBuilder {
+        self.trace_output = Some(Box::new(trace));
+        self
+    }
+
+    /// Loads `source` as `Interpreter::with_config` does.
+    pub fn build(self, source: &[u8]) -> Result<Interpreter, InitError> {
+        let interpreter = Interpreter::with_config(source, self.config.clone())?;
+        Ok(self.attach(interpreter))
+    }
+
+    /// Loads `source` as `Interpreter::from_normalized` does.
+    pub fn build_normalized(self, source: &[u8]) -> Result<Interpreter, InitError> {
+        let interpreter = Interpreter::from_normalized(source, self.config.clone())?;
+        Ok(self.attach(interpreter))
+    }
+
+    /// Starts from a memory image as `Interpreter::from_image` does.
+    pub fn build_image(self, mem: Box<Memory>) -> Interpreter {
+        let interpreter = Interpreter::from_image(mem, self.config.clone());
+        self.attach(interpreter)
+    }
+
+    /// Hands over the breakpoints and I/O.
+    fn attach(self, mut interpreter: Interpreter) -> Interpreter {
+        interpreter.breakpoints.extend(self.breakpoints);
+
+        #[cfg(feature = "std")]
+        {
+            interpreter.input = self.input;
+            interpreter.output = self.output;
+            interpreter.trace_output = self.trace_output;
+        }
+
+        interpreter
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Loader
+////////////////////////////////////////////////////////////////////////////////
+
+/// Copies `input` into `mem` and fills the rest of memory with the crazy
+/// operation, returning the length of the program.
+///
+/// Each instruction is decrypted according to its position, so it matters
+/// exactly which bytes count toward the program length. All of them do,
+/// except for:
+///
+/// * ASCII whitespace: space, tab, LF, vertical tab, form feed and CR
+/// * a UTF-8 byte order mark at the very start of `input`
+/// * NUL bytes at the very end of `input`
+///
+/// Unprintable bytes are loaded as data. Printable ones must decode to one
+/// of the `OPCODES`.
+pub fn init(input: &[u8], mem: &mut Memory) -> Result<usize, InitError> {
+    load(input, mem, None)
+}
+
+/// Like `init`, but skips printable bytes that don't decode to one of the
+/// `OPCODES` instead of failing, for programs that picked up stray
+/// characters somewhere. Returns the `InitError::InvalidChar` for each
+/// byte skipped along with the length.
+///
+/// A skipped byte doesn't count toward the program length, so every
+/// instruction after it is decrypted one position earlier than where it
+/// was written. The program may well not do what its author meant.
+pub fn init_lenient(input: &[u8], mem: &mut Memory) -> Result<(usize, Vec<InitError>), InitError> {
+    let mut skipped = Vec::new();
+    let len = load(input, mem, Some(&mut skipped))?;
+
+    Ok((len, skipped))
+}
+
+/// Does the work of `init`, and of `init_lenient` when there's somewhere
+/// to put the skipped bytes.
+fn load(input: &[u8], mem: &mut Memory, mut skipped: Option<&mut Vec<InitError>>) -> Result<usize, InitError> {
+    let mut i = 0;
+
+    for (loc, b) in program_bytes(input) {
+        if is_space(b) {
+            continue;
+        }
+
+        // check this first so that nothing past the end of memory is
+        // examined, let alone written
+        if i >= MAX_MEMORY {
+            return Err(SourceTooLong);
+        }
+
+        if is_printable(b as usize) && !OPCODES.contains(decode(b as usize, i)) {
+            match skipped {
+                Some(ref mut skipped) => {
+                    skipped.push(invalid_char(input, loc, i));
+                    continue;
+                }
+                None => return Err(invalid_char(input, loc, i)),
+            }
+        }
+
+        mem[i] = b as u16;
+        i += 1;
+    }
+
+    // the C version does not check for these cases; see `init_strict`
+    if i == 0 {
+        return Err(SourceEmpty);
+    }
+
+    if i < 2 {
+        return Err(SourceTooShort);
+    }
+
+    fill_memory(mem, i, MemoryFill::CrazyOp)?;
+
+    Ok(i)
+}
+
+/// Like `init`, but accepts exactly what the C reference loader accepts,
+/// for checking that a program behaves the same way under both. Where
+/// they differ:
+///
+/// * a byte order mark and trailing NULs are loaded as data, not skipped
+/// * empty and one-instruction programs load; C fills memory from cells
+///   before the start, which is undefined, and here they are taken to be 0
+/// * a program that is too long but has an invalid character right past
+///   the end of memory reports the character, which C checks first
+pub fn init_strict(input: &[u8], mem: &mut Memory) -> Result<usize, InitError> {
+    let mut i = 0;
+
+    for (loc, &b) in input.iter().enumerate() {
+        if is_space(b) {
+            continue;
+        }
+
+        if is_printable(b as usize) && !OPCODES.contains(decode(b as usize, i)) {
+            return Err(invalid_char(input, loc, i));
+        }
+
+        if i >= MAX_MEMORY {
+            return Err(SourceTooLong);
+        }
+
+        mem[i] = b as u16;
+        i += 1;
+    }
+
+    fill_memory(mem, i, MemoryFill::CrazyOp)?;
+
+    Ok(i)
+}
+
+/// Fills every cell from `len` to the end of memory according to `fill`.
+/// The loaders use `MemoryFill::CrazyOp`, taking any cell before address 0
+/// to be 0. Fails, leaving `mem` unchanged, if the fill value is invalid.
+pub fn fill_memory(mem: &mut Memory, len: usize, fill: MemoryFill) -> Result<(), InitError> {
+    match fill {
+        MemoryFill::CrazyOp => {
+            for n in len..MAX_MEMORY {
+                let before = |k: usize| n.checked_sub(k).map_or(0, |addr| mem[addr] as usize);
+                mem[n] = crazy_op(before(1), before(2)) as u16;
+            }
+        }
+        MemoryFill::Constant(value) => {
+            if value as usize >= MAX_MEMORY {
+                return Err(InvalidFill(value));
+            }
+
+            mem[len.min(MAX_MEMORY)..].fill(value);
+        }
+    }
+
+    Ok(())
+}
+
+/// Like `init`, but for a program written in normalized form, where each
+/// instruction is one of the `OPCODES` characters rather than a byte that
+/// is encrypted according to its position. The same bytes are ignored as
+/// in `init`, and so is everything from a `#` to the end of its line.
+pub fn init_normalized(input: &[u8], mem: &mut Memory) -> Result<usize, InitError> {
+    let mut source = Vec::with_capacity(input.len());
+    let mut comment = false;
+
+    for (loc, b) in program_bytes(input) {
+        match b {
+            b'\n' => comment = false,
+            b'#' => comment = true,
+            _ => {}
+        }
+
+        if comment || is_space(b) {
+            continue;
+        }
+
+        if !OPCODES.contains(b as char) {
+            return Err(invalid_char(input, loc, source.len()));
+        }
+
+        source.push(encode(b as char, source.len()).unwrap());
+    }
+
+    init(&source, mem)
+}
+
+/// The inverse of `init_normalized`: translates a program into normalized
+/// form. Bytes that `init` ignores are skipped, so the result has one
+/// character per instruction.
+pub fn to_normalized(source: &[u8]) -> Result<String, InitError> {
+    let mut normalized = String::with_capacity(source.len());
+
+    for (loc, b) in program_bytes(source) {
+        if is_space(b) {
+            continue;
+        }
+
+        if normalized.len() >= MAX_MEMORY {
+            return Err(SourceTooLong);
+        }
+
+        // unprintable bytes can be loaded, but they have no normalized form
+        if !is_printable(b as usize) {
+            return Err(invalid_char(source, loc, normalized.len()));
+        }
+
+        let op = decode(b as usize, normalized.len());
+
+        if !OPCODES.contains(op) {
+            return Err(invalid_char(source, loc, normalized.len()));
+        }
+
+        normalized.push(op);
+    }
+
+    Ok(normalized)
+}
+
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Returns the bytes of `input` with their offsets, leaving out a leading
+/// byte order mark and any trailing NULs.
+fn program_bytes(input: &[u8]) -> impl Iterator<Item = (usize, u8)> + '_ {
+    let start = if input.starts_with(BOM) { BOM.len() } else { 0 };
+    let end = input.iter().rposition(|&b| b != 0).map_or(0, |n| n + 1);
+
+    input[..end].iter().copied().enumerate().skip(start)
+}
+
+/// Matches C's `isspace` in the default locale.
+#[inline]
+fn is_space(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\n' | b'\x0B' | b'\x0C' | b'\r')
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Disassembler
+////////////////////////////////////////////////////////////////////////////////
+
+/// Marks cells in a disassembly that can't be executed.
+pub const DATA: char = '.';
+
+/// Decodes `count` cells starting at `start` into the instructions they
+/// would execute as if `r_c` pointed at them. Printable cells that don't
+/// decode to one of `ji*p</v` are nops and show up as `o`. Cells that
+/// aren't printable show up as `DATA`. The listing stops at the end of
+/// memory.
+pub fn disassemble(mem: &Memory, start: usize, count: usize) -> Vec<(usize, char)> {
+    let end = start.saturating_add(count).min(MAX_MEMORY);
+
+    (start..end).map(|addr| (addr, decode_cell(mem, addr))).collect()
+}
+
+/// Decodes a single cell for `disassemble`.
+fn decode_cell(mem: &Memory, addr: usize) -> char {
+    if !is_printable(mem[addr] as usize) {
+        DATA
+    } else {
+        match decode(mem[addr] as usize, addr) {
+            op @ ('j' | 'i' | '*' | 'p' | '<' | '/' | 'v') => op,
+            _ => 'o',
+        }
+    }
+}
+
+/// Counts the instructions that the first `len` cells decode to, in the
+/// order of `OPCODES`, with nops counted as `o` and data left out.
+///
+/// Each cell is decoded where it was loaded. Running the program
+/// re-encrypts cells and may write new ones, so this says nothing about
+/// what will actually execute.
+pub fn opcode_histogram(mem: &Memory, len: usize) -> [usize; 8] {
+    let mut counts = [0; 8];
+
+    for (_, op) in disassemble(mem, 0, len) {
+        if let Some(index) = OPCODES.find(op) {
+            counts[index] += 1;
+        }
+    }
+
+    counts
+}
+
+/// Whether any of the first `len` cells decodes to `op` where it was
+/// loaded. The same caveat applies as for `opcode_histogram`.
+pub fn contains_opcode(mem: &Memory, len: usize, op: char) -> bool {
+    op != DATA && disassemble(mem, 0, len).iter().any(|&(_, c)| c == op)
+}
+
+/// Lists the cells in `range`, one per line: the address, the value, the
+/// instruction as shown by `disassemble` and the character that XLAT2 will
+/// re-encrypt the cell to once it has been executed. Cells that aren't
+/// printable are shown as `DATA` with no re-encryption target.
+pub fn listing(mem: &Memory, range: Range<usize>) -> String {
+    let count = range.end.saturating_sub(range.start);
+    let mut listing = String::new();
+
+    for (addr, op) in disassemble(mem, range.start, count) {
+        let value = mem[addr] as usize;
+
+        let line = if op == DATA {
+            format!("{:>5}  {:>5}  {}\n", addr, value, op)
+        } else {
+            let target = XLAT2[value - 33] as char;
+            format!("{:>5}  {:>5}  {}  {}\n", addr, value, op, target)
+        };
+
+        listing.push_str(&line);
+    }
+
+    listing
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Memory Dumps
+////////////////////////////////////////////////////////////////////////////////
+
+/// Writes every memory cell to `out`, one per line, as the decimal address
+/// followed by the decimal value.
+#[cfg(feature = "std")]
+pub fn dump_memory(mem: &Memory, out: &mut dyn Write) -> io::Result<()> {
+    for (addr, value) in mem.iter().enumerate() {
+        writeln!(out, "{:>5} {:>5}", addr, value)?;
+    }
+
+    Ok(())
+}
+
+/// Reads a memory image in the format written by `dump_memory`. Every
+/// address must appear in order and every value must be less than
+/// `MAX_MEMORY`.
+#[cfg(feature = "std")]
+pub fn read_image(input: &mut dyn BufRead) -> io::Result<Box<Memory>> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+    let mut mem = Interpreter::alloc();
+    let mut count = 0;
+
+    for line in input.lines() {
+        let line = line?;
+
+        if count >= MAX_MEMORY {
+            return Err(invalid("Memory image has too many cells.".to_string()));
+        }
+
+        let mut fields = line.split_whitespace().map(str::parse::<usize>);
+        let (addr, value) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(Ok(addr)), Some(Ok(value)), None) => (addr, value),
+            _ => return Err(invalid(format!("Malformed line in memory image: {}", line))),
+        };
+
+        if addr != count {
+            return Err(invalid(format!("Expected address {} in memory image, \
+                                        found {}.", count, addr)));
+        }
+
+        if value >= MAX_MEMORY {
+            return Err(invalid(format!("Value out of range at address {}: {}",
+                                       addr, value)));
+        }
+
+        mem[addr] = value as u16;
+        count += 1;
+    }
+
+    if count < MAX_MEMORY {
+        return Err(invalid("Memory image has too few cells.".to_string()));
+    }
+
+    Ok(mem)
+}
+
+/// Reads a memory image from the file at `path`. See `read_image`.
+#[cfg(feature = "std")]
+pub fn load_image(path: &Path) -> io::Result<Box<Memory>> {
+    let file = std::fs::File::open(path)?;
+    read_image(&mut BufReader::new(file))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Interpreter Functions
+////////////////////////////////////////////////////////////////////////////////
+
+#[inline]
+pub fn is_printable(c: usize) -> bool {
+    32 < c && c < 127
+}
+
+/// Returns the XLAT1 character that the printable `value` stands for when
+/// it is executed from `addr`. Panics if `value` is not printable.
+#[inline]
+pub fn decode(value: usize, addr: usize) -> char {
+    debug_assert!(is_printable(value));
+    DECODE[addr % 94][value - 33] as char
+}
+
+const fn decode_table() -> [[u8; 94]; 94] {
+    let mut table = [[0; 94]; 94];
+    let mut offset = 0;
+
+    while offset < 94 {
+        let mut index = 0;
+
+        while index < 94 {
+            table[offset][index] = XLAT1[(index + offset) % 94];
+            index += 1;
+        }
+
+        offset += 1;
+    }
+
+    table
+}
+
+#[inline]
+fn next_addr(addr: usize) -> usize {
+    // cheaper than taking the remainder
+    if addr == MAX_MEMORY - 1 { 0 } else { addr + 1 }
+}
+
+/// Reads one byte for the `/` instruction, retrying reads that fail with
+/// `ErrorKind::Interrupted`. `None` means EOF.
+#[cfg(feature = "std")]
+pub(crate) fn read_byte(input: &mut dyn Read) -> io::Result<Option<u8>> {
+    let mut buf = [0u8];
+
+    loop {
+        match input.read(&mut buf) {
+            Ok(1) => return Ok(Some(buf[0])),
+            Ok(_) => return Ok(None),
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// The inverse of `decode`: returns the printable byte that stands for `op`
+/// when it is executed from `addr`, or `None` if `op` isn't in XLAT1.
+pub fn encode(op: char, addr: usize) -> Option<u8> {
+    let index = XLAT1.iter().position(|&c| c as char == op)?;
+    Some(((index + 94 - addr % 94) % 94 + 33) as u8)
+}
+
+/// Rotates the ten-trit word `x` one trit to the right: every trit moves
+/// to the next lower position and the least significant trit becomes the
+/// most significant one. Applying it ten times gives back `x`.
+///
+/// The `*` instruction computes `tri_rotate([r_d])`. `x` must be less than
+/// `MAX_MEMORY`.
+#[inline]
+pub fn tri_rotate(x: usize) -> usize {
+    debug_assert!(x < MAX_MEMORY);
+
+    // shift right and move the rightmost trit to the front
+    let (q, r) = (x / 3, x % 3);
+    q + r * 19683 // 3^9 == 19683
+}
+
+/// The "crazy" operation, applied trit by trit to the ten-trit words `x`
+/// and `y`. Each trit of the result is looked up in this table:
+///
+/// ```text
+///         x = 0  x = 1  x = 2
+/// y = 0     1      0      0
+/// y = 1     1      0      2
+/// y = 2     2      2      1
+/// ```
+///
+/// The `p` instruction computes `crazy_op(r_a, [r_d])` and the loader
+/// fills memory past the program with `crazy_op([n - 1], [n - 2])`. Both
+/// arguments must be less than `MAX_MEMORY`.
+#[inline]
+pub fn crazy_op(x: usize, y: usize) -> usize {
+    debug_assert!(x < MAX_MEMORY && y < MAX_MEMORY);
+
+    // look up two trits at a time
+    static P9: [usize; 5] = [1, 9, 81, 729, 6561];
+    static O: [[usize; 9]; 9] = [
+        [4, 3, 3, 1, 0, 0, 1, 0, 0],
+        [4, 3, 5, 1, 0, 2, 1, 0, 2],
+        [5, 5, 4, 2, 2, 1, 2, 2, 1],
+        [4, 3, 3, 1, 0, 0, 7, 6, 6],
+        [4, 3, 5, 1, 0, 2, 7, 6, 8],
+        [5, 5, 4, 2, 2, 1, 8, 8, 7],
+        [7, 6, 6, 7, 6, 6, 4, 3, 3],
+        [7, 6, 8, 7, 6, 8, 4, 3, 5],
+        [8, 8, 7, 8, 8, 7, 5, 5, 4],
+    ];
+
+    (0..5).fold(0, |sum, i| sum + O[y / P9[i] % 9][x / P9[i] % 9] * P9[i])
+}
+
+/// Splits the ten-trit word `value` into its trits, most significant
+/// first. `value` must be less than `MAX_MEMORY`.
+pub fn to_trits(value: usize) -> [u8; 10] {
+    debug_assert!(value < MAX_MEMORY);
+
+    let mut trits = [0; 10];
+    let mut rest = value;
+
+    for trit in trits.iter_mut().rev() {
+        *trit = (rest % 3) as u8;
+        rest /= 3;
+    }
+
+    trits
+}
+
+/// Displays a ten-trit word as the ten base-3 digits from `to_trits`, so
+/// that 29525 shows up as `1111111112`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Trits(pub usize);
+
+impl fmt::Display for Trits {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for trit in to_trits(self.0) {
+            write!(f, "{}", trit)?;
+        }
+
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Tests
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn rotate_test() {
+        let input = 17;
+        let rotated = (0..10).fold(input, |prev, _| tri_rotate(prev));
+        assert_eq!(input, rotated);
+    }
+
+    #[test]
+    fn rotate_identity_test() {
+        for x in (0..MAX_MEMORY).step_by(7) {
+            let rotated = (0..10).fold(x, |prev, _| tri_rotate(prev));
+            assert_eq!(rotated, x);
+        }
+
+        assert_eq!(tri_rotate(0), 0);
+        assert_eq!(tri_rotate(1), 19683);
+        assert_eq!(tri_rotate(3), 1);
+        assert_eq!(tri_rotate(MAX_MEMORY - 1), MAX_MEMORY - 1);
+    }
+
+    #[test]
+    fn rotate_examples_test() {
+        // (x, rotated) worked out by hand, with the trits alongside
+        let examples = [
+            (2, 39366),     // 0000000002 -> 2000000000
+            (5, 39367),     // 0000000012 -> 2000000001
+            (29524, 29524), // 1111111111 -> 1111111111
+            (59047, 39365), // 2222222221 -> 1222222222
+            (12345, 4115),  // 0121221020 -> 0012122102
+            (40000, 33016), // 2000212111 -> 1200021211
+        ];
+
+        for (x, rotated) in examples {
+            assert_eq!(tri_rotate(x), rotated, "tri_rotate({})", x);
+        }
+    }
+
+    /// Cells picked by xorshift64, seeded so that any failure can be
+    /// reproduced.
+    pub(crate) fn random_cells(mut state: u64, max: usize) -> impl FnMut() -> usize {
+        move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize % max
+        }
+    }
+
+    /// The crazy operation on words of `trits` trits as the spec defines
+    /// it, one trit at a time.
+    pub(crate) fn reference_crazy_op(mut x: usize, mut y: usize, trits: u32) -> usize {
+        // TABLE[trit of y][trit of x]
+        const TABLE: [[usize; 3]; 3] = [[1, 0, 0], [1, 0, 2], [2, 2, 1]];
+        let mut result = 0;
+        let mut power = 1;
+
+        for _ in 0..trits {
+            result += TABLE[y % 3][x % 3] * power;
+            x /= 3;
+            y /= 3;
+            power *= 3;
+        }
+
+        result
+    }
+
+    #[test]
+    fn rotate_random_test() {
+        let mut next = random_cells(0x9E37_79B9_7F4A_7C15, MAX_MEMORY);
+
+        for _ in 0..10_000 {
+            let x = next();
+            let (trits, rotated) = (to_trits(x), to_trits(tri_rotate(x)));
+
+            // the last trit wraps around to the front and the rest shift over
+            assert_eq!(rotated[0], trits[9], "tri_rotate({})", x);
+            assert_eq!(rotated[1..], trits[..9], "tri_rotate({})", x);
+
+            let back = (0..10).fold(x, |prev, _| tri_rotate(prev));
+            assert_eq!(back, x);
+        }
+    }
+
+    #[test]
+    fn to_trits_test() {
+        assert_eq!(to_trits(0), [0; 10]);
+        assert_eq!(to_trits(5), [0, 0, 0, 0, 0, 0, 0, 0, 1, 2]);
+        assert_eq!(to_trits(MAX_MEMORY - 1), [2; 10]);
+        assert_eq!(Trits(29525).to_string(), "1111111112");
+
+        // rotating moves the last trit to the front
+        assert_eq!(Trits(tri_rotate(5)).to_string(), "2000000001");
+    }
+
+    #[test]
+    fn crazy_op_test() {
+        for x in (0..MAX_MEMORY).step_by(97) {
+            for y in (0..MAX_MEMORY).step_by(89) {
+                assert_eq!(crazy_op(x, y), reference_crazy_op(x, y, 10));
+            }
+        }
+
+        let max = MAX_MEMORY - 1;
+        assert_eq!(crazy_op(max, max), reference_crazy_op(max, max, 10));
+    }
+
+    #[test]
+    fn crazy_op_random_test() {
+        let mut next = random_cells(0x2545_F491_4F6C_DD1D, MAX_MEMORY);
+
+        for _ in 0..100_000 {
+            let (x, y) = (next(), next());
+            assert_eq!(crazy_op(x, y), reference_crazy_op(x, y, 10), "crazy_op({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn send_test() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Interpreter>();
+    }
+
+    #[test]
+    fn clone_test() {
+        let mut interpreter = Interpreter::new(include_bytes!("../programs/hello-world.mb")).unwrap();
+
+        for _ in 0..20 {
+            interpreter.step();
+        }
+
+        let mut copy = interpreter.clone();
+        assert_eq!(copy.save_state(), interpreter.save_state());
+
+        // run one to the end and leave the other where it was
+        let mut output = Vec::new();
+        interpreter.execute(&mut io::empty(), &mut output).unwrap();
+        assert_ne!(copy.save_state(), interpreter.save_state());
+        assert_eq!(copy.instruction_count(), 20);
+
+        let mut copied_output = Vec::new();
+        copy.execute(&mut io::empty(), &mut copied_output).unwrap();
+        assert_eq!(copied_output, output);
+        assert_eq!(copy.save_state(), interpreter.save_state());
+    }
+
+    #[test]
+    fn mem_view_test() {
+        let mut interpreter = Interpreter::new(include_bytes!("../programs/hello-world.mb")).unwrap();
+        assert_eq!(interpreter.mem_view().len(), MAX_MEMORY);
+
+        interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap();
+        let view = interpreter.mem_view();
+        assert_eq!(view, &interpreter.save_state().mem[..]);
+        assert!((0..MAX_MEMORY).step_by(101).all(|addr| view[addr] as usize == interpreter.mem(addr)));
+    }
+
+    #[test]
+    fn init_error_test() {
+        let error: Box<dyn Error> = Box::new(Interpreter::new(b"(").err().unwrap());
+        assert_eq!(error.to_string(), "Source program is too short.");
+
+        for source in [&b""[..], b" \t\r\n\n  "] {
+            assert!(matches!(Interpreter::new(source), Err(SourceEmpty)));
+        }
+
+        let error: Box<dyn Error> = Box::new(Interpreter::new(b"\n").err().unwrap());
+        assert_eq!(error.to_string(), "Source program is empty.");
+    }
+
+    #[test]
+    fn memory_fill_test() {
+        let source = include_bytes!("../tests/fixtures/hello-world.mb");
+        let standard = Interpreter::new(source).unwrap();
+        let config = Config { memory_fill: MemoryFill::Constant(0), ..Config::default() };
+        let zeroed = Interpreter::with_config(source, config).unwrap();
+
+        let len = standard.instructions().count();
+        assert!((0..len).all(|addr| zeroed.mem(addr) == standard.mem(addr)));
+        assert!((len..MAX_MEMORY).all(|addr| zeroed.mem(addr) == 0));
+
+        // the standard fill is what the loader does anyway
+        let mut mem = Interpreter::alloc();
+        init(source, &mut mem).unwrap();
+        fill_memory(&mut mem, len, MemoryFill::Constant(7)).unwrap();
+        fill_memory(&mut mem, len, MemoryFill::CrazyOp).unwrap();
+        assert!((0..MAX_MEMORY).all(|addr| mem[addr] as usize == standard.mem(addr)));
+
+        // an invalid value is an error rather than a panic
+        let invalid = MemoryFill::Constant(MAX_MEMORY as u16);
+        assert!(matches!(fill_memory(&mut mem, len, invalid), Err(InvalidFill(59049))));
+        assert!((0..MAX_MEMORY).all(|addr| mem[addr] as usize == standard.mem(addr)));
+
+        let config = Config { memory_fill: invalid, ..Config::default() };
+        assert!(matches!(Interpreter::with_config(source, config), Err(InvalidFill(59049))));
+    }
+
+    #[test]
+    fn conversion_test() {
+        let source = include_bytes!("../tests/fixtures/hello-world.mb");
+        let parsed: Interpreter = core::str::from_utf8(source).unwrap().parse().unwrap();
+        let converted = Interpreter::try_from(&source[..]).unwrap();
+
+        let mut output = Vec::new();
+        converted.clone().execute(&mut io::empty(), &mut output).unwrap();
+        assert_eq!(output, b"Hello World!");
+        assert!((0..MAX_MEMORY).all(|addr| parsed.mem(addr) == converted.mem(addr)));
+
+        assert!(matches!("".parse::<Interpreter>(), Err(InitError::SourceEmpty)));
+        let short = [encode('v', 0).unwrap()];
+        assert!(matches!(Interpreter::try_from(&short[..]), Err(InitError::SourceTooShort)));
+    }
+
+    #[test]
+    fn init_lenient_test() {
+        let source = include_bytes!("../tests/fixtures/hello-world.mb");
+        let mut expected = Interpreter::alloc();
+        let len = init(source, &mut expected).unwrap();
+
+        // a stray character right after the first instruction
+        let stray = (b'!'..=b'~').find(|&b| !OPCODES.contains(decode(b as usize, 1))).unwrap();
+        let mut scraped = source.to_vec();
+        scraped.insert(1, stray);
+        assert!(matches!(init(&scraped, &mut Interpreter::alloc()), Err(InvalidChar { .. })));
+
+        let mut mem = Interpreter::alloc();
+        let (lenient_len, skipped) = init_lenient(&scraped, &mut mem).unwrap();
+        assert_eq!(lenient_len, len);
+        assert!(mem[..] == expected[..]);
+        assert!(matches!(skipped[..], [InvalidChar { offset: 1, index: 1, .. }]));
+
+        let config = Config { lenient: true, ..Config::default() };
+        let mut interpreter = Interpreter::with_config(&scraped, config).unwrap();
+        let mut output = Vec::new();
+        interpreter.execute(&mut io::empty(), &mut output).unwrap();
+        assert_eq!(output, b"Hello World!");
+    }
+
+    #[test]
+    fn init_strict_test() {
+        let mut mem: Box<Memory> = vec![0; MAX_MEMORY].try_into().unwrap();
+        let mut strict: Box<Memory> = vec![0; MAX_MEMORY].try_into().unwrap();
+
+        // the two agree on ordinary programs
+        let source = include_bytes!("../tests/fixtures/hello-world.mb");
+        assert_eq!(init(source, &mut mem).unwrap(), init_strict(source, &mut strict).unwrap());
+        assert_eq!(mem, strict);
+
+        // a byte order mark and trailing NULs are data to C
+        let mut source = BOM.to_vec();
+        source.extend([encode('v', 3).unwrap(), 0]);
+        assert_eq!(init_strict(&source, &mut strict).unwrap(), 5);
+        assert_eq!(strict[0], 0xEF);
+        assert_eq!(strict[4], 0);
+
+        // short programs load, filling from zeros
+        assert_eq!(init_strict(b"", &mut strict).unwrap(), 0);
+        assert_eq!(strict[0] as usize, crazy_op(0, 0));
+        assert_eq!(init_strict(b" ( ", &mut strict).unwrap(), 1);
+        assert_eq!(strict[1] as usize, crazy_op(40, 0));
+
+        // C checks the character before the length
+        let mut long = vec![0x80; MAX_MEMORY];
+        long.push(b'\x80');
+        assert!(matches!(init_strict(&long, &mut strict), Err(SourceTooLong)));
+        long.pop();
+        let invalid = (33..127).find(|&b| !OPCODES.contains(decode(b as usize, MAX_MEMORY))).unwrap();
+        long.push(invalid);
+        assert!(matches!(init(&long, &mut mem), Err(SourceTooLong)));
+        assert!(matches!(init_strict(&long, &mut strict), Err(InvalidChar { index: MAX_MEMORY, .. })));
+    }
+
+    #[test]
+    fn two_instruction_fill_test() {
+        // `j` then `v`, the shortest program that loads
+        let interpreter = Interpreter::new(b"(P").unwrap();
+        assert_eq!(interpreter.mem(0), 40);
+        assert_eq!(interpreter.mem(1), 80);
+
+        // 40 is 0000001111 in ternary and 80 is 0000002222, so the first
+        // fill is crazy_op(80, 40) = 1111112222 = 29564
+        assert_eq!(interpreter.mem(2), 29564);
+        assert_eq!(interpreter.mem(3), crazy_op(29564, 80));
+        assert_eq!(interpreter.mem(3), 40);
+        assert_eq!(interpreter.mem(4), 29564);
+        assert_eq!(interpreter.mem(5), 80);
+
+        assert!(matches!(Interpreter::new(b"("), Err(SourceTooShort)));
+    }
+
+    #[test]
+    fn xlat_permutation_test() {
+        for table in [XLAT1, XLAT2] {
+            assert_eq!(table.len(), 94);
+
+            let mut sorted = table.to_vec();
+            sorted.sort_unstable();
+            assert_eq!(sorted, (33..127).collect::<Vec<u8>>());
+        }
+    }
+
+    #[test]
+    fn reencrypt_test() {
+        for value in 33..127 {
+            let mut mem = Box::new([0; MAX_MEMORY]);
+            mem[0] = value as u16;
+
+            let mut interpreter = Interpreter::from_image(mem, Config::default());
+            interpreter.advance();
+
+            let reencrypted = interpreter.mem(0);
+            assert_eq!(reencrypted, XLAT2[value - 33] as usize);
+            assert!(is_printable(reencrypted), "{} became {}", value, reencrypted);
+        }
+    }
+
+    #[test]
+    fn decode_table_test() {
+        for value in 33..127 {
+            for addr in 0..94 * 3 {
+                let expected = XLAT1[(value - 33 + addr) % 94] as char;
+                assert_eq!(decode(value, addr), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn run_until_event_test() {
+        let source = include_bytes!("../tests/fixtures/hello-world.mb");
+        let mut interpreter = Interpreter::new(source).unwrap();
+        let mut output = Vec::new();
+
+        loop {
+            match interpreter.run_until_event() {
+                Output(b) => output.push(b),
+                Halted(halt) => {
+                    assert_eq!(halt, Halt::Stopped);
+                    break;
+                }
+                result => panic!("unexpected {:?}", result),
+            }
+        }
+
+        assert_eq!(output, b"Hello World!");
+
+        // a pending `/` is reported again until it gets its byte
+        let mut interpreter = Interpreter::from_normalized(b"o/<v", Config::default()).unwrap();
+        assert_eq!(interpreter.run_until_event(), NeedsInput);
+        assert_eq!(interpreter.run_until_event(), NeedsInput);
+        interpreter.input(Some(b'x'));
+        assert_eq!(interpreter.run_until_event(), Output(b'x'));
+
+        interpreter.reset();
+        interpreter.add_breakpoint(1);
+        assert_eq!(interpreter.run_until_event(), Halted(Halt::Breakpoint(1)));
+        assert_eq!(interpreter.run_until_event(), NeedsInput);
+
+        let config = Config { max_steps: Some(3), ..Config::default() };
+        let mut interpreter = Interpreter::from_normalized(b"ooooov", config).unwrap();
+        assert_eq!(interpreter.run_until_event(), Halted(Halt::StepLimitReached));
+        assert_eq!(interpreter.registers().1, 3);
+    }
+
+    #[test]
+    fn watch_test() {
+        // straight-line code has `r_d == r_c`, so the `*` rotates itself
+        let mut interpreter = Interpreter::from_normalized(b"o*v", Config::default()).unwrap();
+        let old = interpreter.mem(1);
+        interpreter.watch(0);
+        interpreter.watch(1);
+
+        // re-encrypting the nop is no write
+        assert_eq!(interpreter.step(), Continue);
+        let result = interpreter.step();
+        assert_eq!(result, MemoryChanged { addr: 1, old, new: interpreter.mem(1) });
+
+        interpreter.reset();
+        interpreter.unwatch(1);
+        assert_eq!(interpreter.step(), Continue);
+        assert_eq!(interpreter.step(), Continue);
+    }
+
+    #[test]
+    fn step_test() {
+        let source = include_bytes!("../programs/hello-world-wikipedia.mb");
+        let mut interpreter = Interpreter::new(source).unwrap();
+        let mut output = Vec::new();
+
+        loop {
+            match interpreter.step() {
+                Continue | MemoryChanged { .. } => {}
+                Output(b) => output.push(b),
+                NeedsInput => interpreter.input(None),
+                Halted(_) => break,
+            }
+        }
+
+        assert_eq!(output, b"Hello World!");
+        assert_eq!(interpreter.instruction_count(), 39);
+
+        interpreter.reset_instruction_count();
+        assert_eq!(interpreter.instruction_count(), 0);
+    }
+
+    #[test]
+    fn execute_test() {
+        let source = include_bytes!("../programs/hello-world.mb");
+        let mut interpreter = Interpreter::new(source).unwrap();
+        let mut output = Vec::new();
+
+        let exit = interpreter.execute(&mut io::empty(), &mut output).unwrap();
+        assert_eq!(output, b"HEllO WORld");
+        assert_eq!(exit.halt, Halt::Stopped);
+    }
+
+    #[test]
+    fn max_steps_test() {
+        let source = include_bytes!("../programs/copy.mb");
+        let config = Config { max_steps: Some(1000), ..Config::default() };
+        let mut interpreter = Interpreter::with_config(source, config).unwrap();
+
+        let exit = interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap();
+        assert_eq!(exit.halt, Halt::StepLimitReached);
+    }
+
+    #[test]
+    fn max_output_test() {
+        let source = include_bytes!("../tests/fixtures/hello-world.mb");
+        let config = Config { max_output: Some(5), ..Config::default() };
+        let mut interpreter = Interpreter::with_config(source, config).unwrap();
+        let mut output = Vec::new();
+
+        let exit = interpreter.execute(&mut io::empty(), &mut output).unwrap();
+        assert_eq!(exit.halt, Halt::OutputLimitReached);
+        assert_eq!(output, b"Hello");
+
+        // the `<` that would have written the sixth byte is not executed
+        assert_eq!(exit.r_c, 33);
+        assert_eq!(interpreter.op(), '<');
+        assert_eq!(interpreter.instruction_count(), 33);
+        assert_eq!(interpreter.output_count(), 5);
+
+        // the limit is not reached by a program that outputs exactly that much
+        let config = Config { max_output: Some(12), ..Config::default() };
+        let mut interpreter = Interpreter::with_config(source, config).unwrap();
+        let exit = interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap();
+        assert_ne!(exit.halt, Halt::OutputLimitReached);
+    }
+
+    #[test]
+    fn fell_through_test() {
+        let mut interpreter = Interpreter::from_normalized(b"oo", Config::default()).unwrap();
+        let exit = interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap();
+
+        assert_eq!(exit.halt, Halt::FellThrough { addr: 2, value: interpreter.mem(2) });
+        assert_eq!(exit.halt.to_string(),
+                   "Program ran into a non-instruction at address 2: 29513 is above 126.");
+    }
+
+    #[test]
+    fn jump_reencryption_test() {
+        // the i at 0 jumps to [0], which is 98
+        let source: Vec<u8> = std::iter::once(b'i')
+            .chain(std::iter::repeat_n(b'o', 98))
+            .chain(std::iter::once(b'v'))
+            .collect();
+
+        let mut interpreter = Interpreter::from_normalized(&source, Config::default()).unwrap();
+        let (jump, target) = (interpreter.mem(0), interpreter.mem(98));
+        assert_eq!(jump, 98);
+
+        assert!(matches!(interpreter.step(), Continue));
+        assert_eq!(interpreter.registers().1, 99);
+
+        // as in C, the target is re-encrypted and the jump is left alone
+        assert_eq!(interpreter.mem(0), jump);
+        assert_eq!(interpreter.mem(98), XLAT2[target - 33] as usize);
+    }
+
+    #[test]
+    fn jump_to_data_test() {
+        // jumps to cell 98, which holds a value too large to re-encrypt
+        let mut interpreter = Interpreter::from_normalized(b"io", Config::default()).unwrap();
+        assert!(interpreter.mem(98) > 126);
+
+        let exit = interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap();
+        assert!(matches!(exit.halt, Halt::FellThrough { addr: 100, .. }));
+        assert_eq!(interpreter.instruction_count(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid state")]
+    fn load_state_invalid_test() {
+        let mut interpreter = Interpreter::new(b"(=").unwrap();
+        let mut state = interpreter.save_state();
+        state.mem[0] = MAX_MEMORY as u16;
+        interpreter.load_state(&state);
+    }
+
+    // a writer whose contents can be read after handing it to a builder
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn builder_test() {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let mut interpreter = InterpreterBuilder::new()
+            .max_output(5)
+            .breakpoint(4)
+            .input(io::empty())
+            .output(Shared(output.clone()))
+            .build(include_bytes!("../tests/fixtures/hello-world.mb"))
+            .unwrap();
+
+        assert_eq!(interpreter.run().unwrap().halt, Halt::Breakpoint(4));
+        assert_eq!(interpreter.run().unwrap().halt, Halt::OutputLimitReached);
+        assert_eq!(*output.lock().unwrap(), b"Hello");
+
+        let interpreter = InterpreterBuilder::new().build_normalized(b"o");
+        assert!(matches!(interpreter, Err(SourceTooShort)));
+    }
+
+    #[test]
+    fn trace_output_test() {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let trace = Arc::new(Mutex::new(Vec::new()));
+        let mut interpreter = InterpreterBuilder::new()
+            .trace(true)
+            .input(io::empty())
+            .output(Shared(output.clone()))
+            .trace_output(Shared(trace.clone()))
+            .build(include_bytes!("../tests/fixtures/hello-world.mb"))
+            .unwrap();
+
+        assert_eq!(interpreter.run().unwrap().halt, Halt::Stopped);
+        assert_eq!(*output.lock().unwrap(), b"Hello World!");
+
+        let trace = String::from_utf8(trace.lock().unwrap().clone()).unwrap();
+        // the final `v` is traced but doesn't count as executed
+        assert_eq!(trace.lines().count() as u64, interpreter.instruction_count() + 1);
+        assert!(trace.starts_with("    0  j  r_a =     0  r_d =     0\n"));
+    }
+
+    #[test]
+    fn trace_color_test() {
+        let trace = |color| {
+            let trace = Arc::new(Mutex::new(Vec::new()));
+            let mut interpreter = InterpreterBuilder::new()
+                .trace(true)
+                .trace_color(color)
+                .trace_output(Shared(trace.clone()))
+                .build_normalized(b"o*<v")
+                .unwrap();
+
+            interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap();
+            let trace = String::from_utf8(trace.lock().unwrap().clone()).unwrap();
+            trace
+        };
+
+        let (plain, colored) = (trace(false), trace(true));
+        assert!(!plain.contains('\x1b'));
+
+        let lines: Vec<&str> = colored.lines().collect();
+        assert!(lines[0].contains("\x1b[1;36mo\x1b[0m"));
+        // `r_d` moved on, and then `*` changed `r_a` for `<` to write
+        assert!(lines[1].ends_with("r_d = \x1b[33m    1\x1b[0m"));
+        assert!(lines[2].contains("r_a = \x1b[32m"));
+
+        let mut stripped = colored.clone();
+        for code in ["\x1b[1;36m", "\x1b[32m", "\x1b[33m", "\x1b[0m"] {
+            stripped = stripped.replace(code, "");
+        }
+        assert_eq!(stripped, plain);
+    }
+
+    #[test]
+    fn output_log_test() {
+        let source = include_bytes!("../tests/fixtures/hello-world.mb");
+        let mut interpreter = InterpreterBuilder::new().log_output(true).build(source).unwrap();
+        let mut output = Vec::new();
+        interpreter.execute(&mut io::empty(), &mut output).unwrap();
+
+        let log = interpreter.output_log().to_vec();
+        assert_eq!(log.iter().map(|&(_, _, b)| b).collect::<Vec<u8>>(), output);
+
+        // stepping through again finds each byte where the log says
+        let mut stepper = Interpreter::new(source).unwrap();
+        for (instruction, addr, b) in log {
+            while stepper.instruction_count() < instruction {
+                stepper.step();
+            }
+
+            assert_eq!(stepper.registers().1, addr);
+            assert_eq!(stepper.step(), Output(b));
+        }
+
+        interpreter.reset();
+        assert!(interpreter.output_log().is_empty());
+        assert!(Interpreter::new(source).unwrap().output_log().is_empty());
+    }
+
+    #[test]
+    fn run_with_test() {
+        let mut interpreter = Interpreter::from_normalized(b"/<v", Config::default()).unwrap();
+        let mut events = Vec::new();
+
+        let exit = interpreter.run_with(|event| {
+            events.push(event);
+            Some(b'x')
+        }).unwrap();
+
+        assert_eq!(exit.halt, Halt::Stopped);
+        assert_eq!(events, [Event::NeedsInput, Event::Output(b'x'), Event::Halted(exit)]);
+
+        let config = Config { max_output: Some(5), ..Config::default() };
+        let mut interpreter = Interpreter::with_config(include_bytes!("../tests/fixtures/hello-world.mb"), config).unwrap();
+        let mut output = Vec::new();
+
+        let exit = interpreter.run_with(|event| {
+            if let Event::Output(b) = event {
+                output.push(b);
+            }
+            None
+        }).unwrap();
+
+        assert_eq!(exit.halt, Halt::OutputLimitReached);
+        assert_eq!(output, b"Hello");
+
+        /// A trace output that always fails.
+        struct Broken;
+
+        impl Write for Broken {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut interpreter = InterpreterBuilder::new()
+            .trace(true)
+            .trace_output(Broken)
+            .build_normalized(b"/<v")
+            .unwrap();
+        let mut events = Vec::new();
+
+        let e = interpreter.run_with(|event| {
+            events.push(event);
+            None
+        }).unwrap_err();
+
+        assert_eq!(e.kind(), io::ErrorKind::BrokenPipe);
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn step_delay_test() {
+        let source = include_bytes!("../programs/copy.mb");
+        let config = Config {
+            max_steps: Some(10),
+            step_delay: Some(Duration::from_millis(2)),
+            ..Config::default()
+        };
+        let mut interpreter = Interpreter::with_config(source, config).unwrap();
+
+        let start = Instant::now();
+        let exit = interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap();
+        assert_eq!(exit.halt, Halt::StepLimitReached);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn timeout_test() {
+        let source = include_bytes!("../programs/copy.mb");
+        let config = Config { max_duration: Some(Duration::ZERO), ..Config::default() };
+        let mut interpreter = Interpreter::with_config(source, config).unwrap();
+
+        let exit = interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap();
+        assert_eq!(exit.halt, Halt::Timeout);
+
+        let config = Config { max_duration: Some(Duration::from_millis(50)), ..Config::default() };
+        let mut interpreter = Interpreter::with_config(source, config).unwrap();
+
+        let exit = interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap();
+        assert_eq!(exit.halt, Halt::Timeout);
+        assert!(interpreter.instruction_count() > 0);
+    }
+
+    #[test]
+    fn breakpoint_test() {
+        let source = include_bytes!("../programs/hello-world.mb");
+        let mut interpreter = Interpreter::new(source).unwrap();
+        let mut output = Vec::new();
+
+        interpreter.add_breakpoint(4);
+        let exit = interpreter.execute(&mut io::empty(), &mut output).unwrap();
+        assert_eq!(exit.halt, Halt::Breakpoint(4));
+        assert_eq!(exit.r_c, 4);
+        assert_eq!(output, b"H");
+
+        interpreter.clear_breakpoints();
+        let exit = interpreter.execute(&mut io::empty(), &mut output).unwrap();
+        assert_eq!(exit.halt, Halt::Stopped);
+        assert_eq!(output, b"HEllO WORld");
+    }
+
+    #[test]
+    fn coverage_test() {
+        let source = include_bytes!("../programs/hello-world.mb");
+        let mut interpreter = Interpreter::new(source).unwrap();
+        interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap();
+        assert!(interpreter.coverage().is_empty());
+
+        let config = Config { coverage: true, ..Config::default() };
+        let mut interpreter = Interpreter::from_normalized(b"oo<v", config).unwrap();
+        let exit = interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap();
+        assert_eq!(exit.halt, Halt::Stopped);
+        assert_eq!(interpreter.coverage().iter().collect::<Vec<_>>(), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn reset_test() {
+        let source = include_bytes!("../tests/fixtures/hello-world.mb");
+        let mut fresh = Interpreter::new(source).unwrap();
+        let mut expected = Vec::new();
+        let expected_exit = fresh.execute(&mut io::empty(), &mut expected).unwrap();
+
+        let mut interpreter = Interpreter::new(source).unwrap();
+        for _ in 0..30 {
+            interpreter.step();
+        }
+        assert_ne!(interpreter.registers(), (0, 0, 0));
+
+        interpreter.reset();
+        assert_eq!(interpreter.registers(), (0, 0, 0));
+        assert_eq!(interpreter.instruction_count(), 0);
+
+        let mut output = Vec::new();
+        let exit = interpreter.execute(&mut io::empty(), &mut output).unwrap();
+        assert_eq!(output, expected);
+        assert_eq!(exit, expected_exit);
+        assert_eq!(interpreter.instruction_count(), fresh.instruction_count());
+    }
+
+    #[test]
+    fn output_count_test() {
+        let source = include_bytes!("../tests/fixtures/hello-world.mb");
+        let mut interpreter = Interpreter::new(source).unwrap();
+        let mut output = Vec::new();
+        interpreter.execute(&mut io::empty(), &mut output).unwrap();
+        assert_eq!(interpreter.output_count(), output.len() as u64);
+
+        // bytes from `step` are the caller's to write
+        interpreter.reset();
+        assert_eq!(interpreter.output_count(), 0);
+        while !matches!(interpreter.step(), StepResult::Halted(_)) {}
+        assert_eq!(interpreter.output_count(), 0);
+    }
+
+    #[test]
+    fn halt_mode_test() {
+        let mut interpreter = Interpreter::from_normalized(b"vo<v", Config::default()).unwrap();
+        assert_eq!(interpreter.step(), Halted(Halt::Stopped));
+        assert_eq!(interpreter.step(), Halted(Halt::Stopped));
+        assert_eq!(interpreter.registers(), (0, 0, 0));
+
+        let config = Config { halt_mode: HaltMode::Resumable, ..Config::default() };
+        let mut interpreter = Interpreter::from_normalized(b"vo<v", config).unwrap();
+        assert_eq!(interpreter.step(), Halted(Halt::Stopped));
+        assert_eq!(interpreter.registers(), (0, 0, 0));
+        assert_eq!(interpreter.step(), Continue);
+        assert_eq!(interpreter.registers(), (0, 1, 1));
+
+        let mut output = Vec::new();
+        let exit = interpreter.execute(&mut io::empty(), &mut output).unwrap();
+        assert_eq!((exit.halt, exit.r_c), (Halt::Stopped, 3));
+        assert_eq!(output, [0]);
+
+        // execute picks up where the last halt left off, running the `v`
+        // and falling through to the fill past the program
+        let count = interpreter.instruction_count();
+        let exit = interpreter.execute(&mut io::empty(), &mut output).unwrap();
+        let value = interpreter.mem(4);
+        assert!(!is_printable(value));
+        assert_eq!(exit, Exit { halt: Halt::FellThrough { addr: 4, value }, r_a: 0, r_c: 4, r_d: 4 });
+        assert_eq!(interpreter.instruction_count(), count + 1);
+        assert_eq!(output, [0]);
+    }
+
+    #[test]
+    fn flush_policy_test() {
+        /// Records how many bytes had been written at each flush.
+        #[derive(Default)]
+        struct Flushes(usize, Vec<usize>);
+
+        impl Write for Flushes {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0 += buf.len();
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                self.1.push(self.0);
+                Ok(())
+            }
+        }
+
+        let source = include_bytes!("../tests/fixtures/cat.mb");
+        let flushes = |policy| {
+            let mut interpreter = InterpreterBuilder::new()
+                .flush_policy(policy)
+                .max_output(2)
+                .build(source)
+                .unwrap();
+            let mut output = Flushes::default();
+            interpreter.execute(&mut &b"ab"[..], &mut output).unwrap();
+            output.1
+        };
+
+        assert!(flushes(FlushPolicy::Never).is_empty());
+        assert_eq!(flushes(FlushPolicy::BeforeInput), [0, 1, 2]);
+        assert_eq!(flushes(FlushPolicy::EveryByte), [0, 1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn last_output_test() {
+        let source = include_bytes!("../tests/fixtures/hello-world.mb");
+        let mut interpreter = Interpreter::new(source).unwrap();
+        interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap();
+        assert_eq!(interpreter.last_output(), b"");
+
+        let mut interpreter = InterpreterBuilder::new().output_history(5).build(source).unwrap();
+        interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap();
+        assert_eq!(interpreter.last_output(), b"orld!");
+
+        // bytes past the output limit were never written
+        let mut interpreter = InterpreterBuilder::new()
+            .output_history(100)
+            .max_output(5)
+            .build(source)
+            .unwrap();
+        interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap();
+        assert_eq!(interpreter.last_output(), b"Hello");
+    }
+
+    #[test]
+    fn watch_writes_test() {
+        // `r_d` keeps pace with `r_c`, so `*` and `p` overwrite themselves
+        let config = Config { watch_writes: true, ..Config::default() };
+        let mut interpreter = Interpreter::from_normalized(b"op*v", config).unwrap();
+        interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap();
+        assert_eq!(interpreter.self_modifications(), [(1, 1), (2, 2)]);
+
+        let mut interpreter = Interpreter::from_normalized(b"op*v", Config::default()).unwrap();
+        interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap();
+        assert!(interpreter.self_modifications().is_empty());
+
+        let trace = Arc::new(Mutex::new(Vec::new()));
+        let mut interpreter = InterpreterBuilder::new()
+            .watch_writes(true)
+            .trace(true)
+            .trace_output(Shared(trace.clone()))
+            .build_normalized(b"op*v")
+            .unwrap();
+        interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap();
+
+        let trace = String::from_utf8(trace.lock().unwrap().clone()).unwrap();
+        let overwrites: Vec<&str> = trace.lines().filter(|line| line.contains("overwrote")).collect();
+        assert_eq!(overwrites, ["       overwrote executed instruction at 1",
+                                "       overwrote executed instruction at 2"]);
+    }
+
+    #[test]
+    fn save_state_test() {
+        let source = include_bytes!("../programs/hello-world.mb");
+        let mut interpreter = Interpreter::new(source).unwrap();
+        let state = interpreter.save_state();
+
+        let mut first = Vec::new();
+        interpreter.execute(&mut io::empty(), &mut first).unwrap();
+        assert_ne!(interpreter.save_state(), state);
+
+        interpreter.load_state(&state);
+        assert_eq!(interpreter.registers(), (0, 0, 0));
+        assert_eq!(interpreter.mem(0), source[0] as usize);
+
+        assert_eq!((interpreter.instruction_count(), interpreter.output_count()), (0, 0));
+
+        let mut second = Vec::new();
+        interpreter.execute(&mut io::empty(), &mut second).unwrap();
+        assert_eq!(first, second);
+
+        // a machine halted at a resumable `v` carries on past it once loaded
+        let config = Config { halt_mode: HaltMode::Resumable, ..Config::default() };
+        let mut interpreter = Interpreter::from_normalized(b"vo<v", config.clone()).unwrap();
+        assert_eq!(interpreter.step(), Halted(Halt::Stopped));
+        let state = interpreter.save_state();
+        assert!(state.stopped);
+
+        let mut loaded = Interpreter::from_normalized(b"vo<v", config).unwrap();
+        loaded.load_state(&state);
+        assert_eq!(loaded.instruction_count(), interpreter.instruction_count());
+        assert_eq!(loaded.step(), Continue);
+        assert_eq!(loaded.registers(), (0, 1, 1));
+    }
+
+    #[test]
+    fn disassemble_test() {
+        let interpreter = Interpreter::new(b"(=a`").unwrap();
+        let listing = interpreter.disassemble(0, 4);
+
+        assert_eq!(listing, vec![(0, 'j'), (1, 'p'), (2, '<'), (3, '<')]);
+        assert_eq!(interpreter.disassemble(MAX_MEMORY - 1, 10).len(), 1);
+    }
+
+    #[test]
+    fn opcode_histogram_test() {
+        let mut mem: Box<Memory> = vec![0; MAX_MEMORY].try_into().unwrap();
+        let len = init_normalized(b"jjoo<v", &mut mem).unwrap();
+
+        assert_eq!(opcode_histogram(&mem, len), [2, 0, 0, 0, 1, 0, 1, 2]);
+        assert!(contains_opcode(&mem, len, 'v'));
+        assert!(!contains_opcode(&mem, len, '/'));
+        assert!(!contains_opcode(&mem, len, DATA));
+
+        // data isn't counted and the cells past the program aren't looked
+        // at unless asked for
+        mem[0] = 0;
+        assert_eq!(opcode_histogram(&mem, len).iter().sum::<usize>(), len - 1);
+        assert!(!contains_opcode(&mem, 1, 'j'));
+    }
+
+    #[test]
+    fn instructions_test() {
+        let interpreter = Interpreter::from_normalized(b"jjoo<v", Config::default()).unwrap();
+        let ops: String = interpreter.instructions().map(|(_, op)| op).collect();
+        assert_eq!(ops, "jjoo<v");
+        assert!(interpreter.instructions().map(|(addr, _)| addr).eq(0..6));
+
+        let image = Interpreter::from_image(Interpreter::alloc(), Config::default());
+        assert_eq!(image.instructions().count(), MAX_MEMORY);
+    }
+
+    #[test]
+    fn listing_test() {
+        let interpreter = Interpreter::new(b"(=a`").unwrap();
+        let expected = "    0     40  j  y\n    \
+                            1     61  p  d\n";
+        assert_eq!(interpreter.listing(0..2), expected);
+
+        let data = Interpreter::from_normalized(b"oo", Config::default()).unwrap();
+        assert_eq!(data.listing(2..3), "    2  29513  .\n");
+        assert_eq!(interpreter.listing(MAX_MEMORY - 1..MAX_MEMORY + 1).lines().count(), 1);
+    }
+
+    #[test]
+    fn normalized_test() {
+        let source = b"jpp<*p<*p<<pp<jpo<*po<*op<*op<jpp<*p<*<voj/ovp/<*j*<</<popi/</oo";
+        let mut interpreter = Interpreter::from_normalized(source, Config::default()).unwrap();
+        let mut output = Vec::new();
+
+        interpreter.execute(&mut io::empty(), &mut output).unwrap();
+        assert_eq!(output, b"Hello World!");
+
+        let result = Interpreter::from_normalized(b"jpx", Config::default());
+        assert!(matches!(result, Err(InvalidChar { c: 'x', offset: 2, .. })));
+
+        let error = Interpreter::from_normalized(b"jp # comment\n  <x", Config::default()).err();
+        assert!(matches!(error, Some(InvalidChar { offset: 16, index: 3, line: 2, column: 4, .. })));
+        assert_eq!(error.unwrap().to_string(),
+                   "Invalid character in source program: 'x' at location: 0x10 \
+                    (line 2, column 4, instruction 3)");
+    }
+
+    #[test]
+    fn opcodes_test() {
+        // each opcode at every position modulo 94
+        let ops: Vec<char> = OPCODES.chars()
+            .flat_map(|op| std::iter::repeat_n(op, 94))
+            .collect();
+        let source: Vec<u8> = ops.iter().enumerate()
+            .map(|(addr, &op)| encode(op, addr).unwrap())
+            .collect();
+
+        let interpreter = Interpreter::new(&source).unwrap();
+        let listing: Vec<char> = interpreter.disassemble(0, ops.len())
+            .into_iter()
+            .map(|(_, op)| op)
+            .collect();
+
+        assert_eq!(listing, ops);
+    }
+
+    fn nops(count: usize) -> Vec<u8> {
+        (0..count).map(|addr| encode('o', addr).unwrap()).collect()
+    }
+
+    #[test]
+    fn source_length_test() {
+        let mut mem = Interpreter::alloc();
+
+        assert!(init(&nops(MAX_MEMORY - 1), &mut mem).is_ok());
+        assert!(init(&nops(MAX_MEMORY), &mut mem).is_ok());
+        assert_eq!(mem[MAX_MEMORY - 1], encode('o', MAX_MEMORY - 1).unwrap() as u16);
+
+        let result = init(&nops(MAX_MEMORY + 1), &mut mem);
+        assert!(matches!(result, Err(SourceTooLong)));
+
+        // the extra byte is too much, regardless of what it decodes to
+        let mut source = nops(MAX_MEMORY);
+        source.push(b'x');
+        assert!(matches!(init(&source, &mut mem), Err(SourceTooLong)));
+    }
+
+    #[test]
+    fn ignored_bytes_test() {
+        let mut mem = Interpreter::alloc();
+
+        // byte order mark, CRLF line endings and trailing NULs
+        let len = init(b"\xEF\xBB\xBF(=\r\na`\0\0", &mut mem).unwrap();
+        assert_eq!(len, 4);
+        assert_eq!(&mem[..4], &[40, 61, 97, 96]);
+
+        // unlike ASCII whitespace, a non-breaking space is data
+        init(b"(=\xA0", &mut mem).unwrap();
+        assert_eq!(mem[2], 0xA0);
+
+        init_normalized(b"jp # comment\n<<", &mut mem).unwrap();
+        assert_eq!(&mem[..4], &[40, 61, 97, 96]);
+    }
+
+    #[test]
+    fn to_normalized_test() {
+        let source = include_bytes!("../programs/hello-world-wikipedia.mb");
+        let normalized = to_normalized(source).unwrap();
+        assert_eq!(normalized, "jpp<*p<*p<<pp<jpo<*po<*op<*op<jpp<*p<*<voj/ovp/<*j*<</<popi/</oo");
+
+        let mut mem = Interpreter::alloc();
+        init_normalized(normalized.as_bytes(), &mut mem).unwrap();
+        assert_eq!(&mem[..source.len()], &Interpreter::new(source).unwrap().mem[..source.len()]);
+    }
+
+    #[test]
+    fn dump_memory_test() {
+        let interpreter = Interpreter::new(b"(=").unwrap();
+        let mut out = Vec::new();
+
+        interpreter.dump_memory(&mut out).unwrap();
+
+        let dump = String::from_utf8(out).unwrap();
+        assert_eq!(dump.lines().count(), MAX_MEMORY);
+        assert_eq!(dump.lines().next(), Some("    0    40"));
+    }
+
+    #[test]
+    fn read_image_test() {
+        let mut interpreter = Interpreter::new(include_bytes!("../programs/hello-world.mb")).unwrap();
+        interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap();
+
+        let mut dump = Vec::new();
+        interpreter.dump_memory(&mut dump).unwrap();
+        let mem = read_image(&mut &dump[..]).unwrap();
+        assert_eq!(mem, interpreter.save_state().mem);
+
+        let error = read_image(&mut &dump[..dump.len() / 2]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let error = read_image(&mut "    0 59049\n".as_bytes()).unwrap_err();
+        assert_eq!(error.to_string(), "Value out of range at address 0: 59049");
+
+        let error = read_image(&mut "    1    40\n".as_bytes()).unwrap_err();
+        assert_eq!(error.to_string(), "Expected address 0 in memory image, found 1.");
+    }
+
+    #[test]
+    fn eof_value_test() {
+        let mut interpreter = Interpreter::from_normalized(b"/v", Config::default()).unwrap();
+        let exit = interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap();
+        assert_eq!(exit.r_a, EOF_VALUE);
+
+        let config = Config { eof_value: 0, ..Config::default() };
+        let mut interpreter = Interpreter::from_normalized(b"/v", config).unwrap();
+        interpreter.r_a = 1;
+        let exit = interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap();
+        assert_eq!(exit.r_a, 0);
+    }
+
+    #[test]
+    fn nonblocking_input_test() {
+        /// Input that has no bytes ready until some are added.
+        struct Starved(Vec<u8>, bool);
+
+        impl Read for Starved {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                if self.0.is_empty() {
+                    return match self.1 {
+                        true => Ok(0),
+                        false => Err(io::ErrorKind::WouldBlock.into()),
+                    };
+                }
+
+                buf[0] = self.0.remove(0);
+                Ok(1)
+            }
+        }
+
+        let mut interpreter = InterpreterBuilder::new()
+            .nonblocking_input(true)
+            .build(include_bytes!("../tests/fixtures/cat.mb"))
+            .unwrap();
+        let mut input = Starved(Vec::new(), false);
+        let mut output = Vec::new();
+
+        let exit = interpreter.execute(&mut input, &mut output).unwrap();
+        assert_eq!(exit.halt, Halt::InputPending);
+        assert!(output.is_empty());
+
+        input.0.extend_from_slice(b"ab");
+        let exit = interpreter.execute(&mut input, &mut output).unwrap();
+        assert_eq!(exit.halt, Halt::InputPending);
+        assert_eq!(output, b"ab");
+
+        // the pending `/` can be completed through the step API too
+        interpreter.input(Some(b'c'));
+        let exit = interpreter.execute(&mut input, &mut output).unwrap();
+        assert_eq!(exit.halt, Halt::InputPending);
+        assert_eq!(output, b"abc");
+
+        // end of input is still end of input
+        input.1 = true;
+        let mut interpreter = Interpreter::from_normalized(b"/v", Config {
+            nonblocking_input: true,
+            ..Config::default()
+        }).unwrap();
+        assert_eq!(interpreter.execute(&mut input, &mut io::sink()).unwrap().halt, Halt::Stopped);
+    }
+
+    #[test]
+    fn read_error_test() {
+        /// Input that fails with each error in turn before reading `b'x'`.
+        struct Failing(Vec<io::ErrorKind>);
+
+        impl Read for Failing {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                if self.0.is_empty() {
+                    buf[0] = b'x';
+                    return Ok(1);
+                }
+
+                Err(self.0.remove(0).into())
+            }
+        }
+
+        let mut interpreter = Interpreter::from_normalized(b"/<v", Config::default()).unwrap();
+        let mut input = Failing(vec![io::ErrorKind::Interrupted, io::ErrorKind::BrokenPipe]);
+        let mut output = Vec::new();
+
+        // the error is returned, not written to the output
+        let e = interpreter.execute(&mut input, &mut output).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::BrokenPipe);
+        assert!(output.is_empty());
+        assert!(input.0.is_empty());
+
+        // the machine waits at the `/` with `r_a` unchanged
+        assert_eq!(interpreter.registers(), (0, 0, 0));
+
+        let exit = interpreter.execute(&mut input, &mut output).unwrap();
+        assert_eq!(exit.halt, Halt::Stopped);
+        assert_eq!(output, b"x");
+    }
+
+    #[test]
+    fn input_policy_test() {
+        let config = Config { input_policy: Some(Arc::new(Unchanged)), ..Config::default() };
+        let mut interpreter = Interpreter::from_normalized(b"/v", config).unwrap();
+        interpreter.r_a = 1234;
+        let exit = interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap();
+        assert_eq!(exit.r_a, 1234);
+
+        // input that is there is unaffected
+        let exit = InterpreterBuilder::new()
+            .input_policy(Unchanged)
+            .build_normalized(b"/v")
+            .unwrap()
+            .execute(&mut &b"A"[..], &mut io::sink())
+            .unwrap();
+        assert_eq!(exit.r_a, b'A' as usize);
+
+        #[derive(Debug)]
+        struct Negate;
+
+        impl InputPolicy for Negate {
+            fn on_eof(&self, r_a: usize) -> usize {
+                MAX_MEMORY - 1 - r_a
+            }
+        }
+
+        let mut interpreter = InterpreterBuilder::new()
+            .input_policy(Negate)
+            .build_normalized(b"/v")
+            .unwrap();
+        interpreter.r_a = 1;
+        let exit = interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap();
+        assert_eq!(exit.r_a, MAX_MEMORY - 2);
+    }
+
+    #[test]
+    fn detect_loops_test() {
+        // 'j' at 0 moves r_d 40 cells ahead of r_c. At 128, 'j' and 'i'
+        // read the data at 167 and 168 to jump both registers back to 128.
+        let mut source: Vec<u8> = (0..167).map(|addr| {
+            let op = match addr {
+                0 | 128 => 'j',
+                129 => 'i',
+                _ => 'o',
+            };
+
+            encode(op, addr).unwrap()
+        }).collect();
+        source.extend_from_slice(&[127, 166]);
+
+        let config = Config { detect_loops: true, ..Config::default() };
+        let mut interpreter = Interpreter::with_config(&source, config).unwrap();
+
+        let exit = interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap();
+        assert_eq!(exit.halt, Halt::LoopDetected);
+        assert_eq!((exit.r_c, exit.r_d), (128, 168));
+    }
+
+    #[test]
+    fn output_mode_test() {
+        let mut interpreter = Interpreter::from_normalized(b"/<v", Config::default()).unwrap();
+        let mut output = Vec::new();
+        interpreter.execute(&mut &[200u8][..], &mut output).unwrap();
+        assert_eq!(output, [200]);
+
+        let config = Config { output_mode: OutputMode::Utf8Lossy, ..Config::default() };
+        let mut interpreter = Interpreter::from_normalized(b"/<v", config).unwrap();
+        let mut output = Vec::new();
+        interpreter.execute(&mut &[200u8][..], &mut output).unwrap();
+        assert_eq!(output, "\u{C8}".as_bytes());
+    }
+
+    #[test]
+    fn input_test() {
+        let source = include_bytes!("../programs/copy.mb");
+        let mut interpreter = Interpreter::new(source).unwrap();
+
+        // copy.mb never halts, so stop it by filling up the output buffer
+        let mut output = [0u8; 5];
+        let result = interpreter.execute(&mut &b"hello"[..], &mut &mut output[..]);
+
+        assert!(result.is_err());
+        assert_eq!(&output, b"hello");
+    }
+}
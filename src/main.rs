@@ -22,222 +22,881 @@
 // CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-/// This is an implementation of the Malbolge interpreter in Rust.
-/// It's basically a translation of the original C version found here:
-/// http://www.lscheffer.com/malbolge_interp.html
-///
-/// For more information about Malbolge:
-///     http://en.wikipedia.org/wiki/Malbolge
-///     http://www.lscheffer.com/malbolge_spec.html
-///
-
-use std::fmt;
 use std::fs::File;
-use std::io::Read;
-use std::path::Path;
+use std::io::{self, BufReader, BufWriter, Cursor, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
 
-static XLAT1: &[u8] = b"+b(29e*j1VMEKLyC})8&m#~W>qxdRp0wkrUo[D7,XTcA\"lI\
-                        .v%{gJh4G\\-=O@5`_3i<?Z';FNQuY]szf$!BS/|t:Pn6^Ha";
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, ValueEnum};
+use malbolge::{malbolge20, Config, Exit, FlushPolicy, Halt, InitError, Interpreter, InterpreterBuilder, Memory, MemoryFill};
+use malbolge::{Trits, MAX_MEMORY, OPCODES};
 
-static XLAT2: &[u8] = b"5z]&gqtyfr$(we4{WP)H-Zn,[%\\3dL+Q;>U!pJS72FhOA1C\
-                        B6v^=I_0/8|jsb9m<.TVac`uY*MK'X~xDl}REokN:#?G\"i@";
+use report::Report;
 
-const MAX_MEMORY: usize = 59049; // == 3^10
-
-// u16 would work here, but this saves a bunch of casting
-type Memory = [usize; MAX_MEMORY];
+mod debugger;
+mod report;
+mod terminal;
 
 ////////////////////////////////////////////////////////////////////////////////
-// InitError
+// main
 ////////////////////////////////////////////////////////////////////////////////
 
-#[derive(Debug)]
-enum InitError {
-    InvalidChar(char, usize),
-    SourceTooShort,
-    SourceTooLong,
-}
+fn main() {
+    let options = parse_args();
+
+    if options.dialect == Dialect::Malbolge20 && !supports_malbolge20(&options) {
+        eprintln!("The malbolge20 dialect only supports --max-steps, --max-output, \
+                  --input, --stdin-string, --raw-input, --echo and --unbuffered.");
+        process::exit(1);
+    }
+
+    match options.source {
+        Source::Programs(ref filenames) => {
+            let mut failed = false;
+
+            for (i, filename) in filenames.iter().enumerate() {
+                if filenames.len() > 1 {
+                    // programs don't always end their output with a newline
+                    if i > 0 {
+                        println!();
+                    }
+
+                    println!("==> {} <==", filename.display());
+                }
 
-use InitError::*;
+                let contents = match load(filename) {
+                    Ok(contents) if options.from_hex => from_hex(&contents),
+                    result => result,
+                };
+
+                let ok = match contents {
+                    Ok(contents) if options.dialect == Dialect::Malbolge20 =>
+                        run20(&contents, &options),
+                    Ok(contents) => run(&contents, filename, &options),
+                    Err(e) if options.json => {
+                        println!("{}", Report::error(e.to_string()).to_json());
+                        false
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        false
+                    }
+                };
+
+                failed |= !ok;
+
+                if !ok && !options.continue_on_error {
+                    break;
+                }
+            }
+
+            // a nonzero status lets scripts and CI see the failure, even
+            // once the rest of the batch has run
+            if failed {
+                process::exit(1);
+            }
+        }
+        Source::Image(ref path) => {
+            let ok = match malbolge::load_image(path) {
+                Ok(mem) => run_image(mem, MAX_MEMORY, &options),
+                Err(e) => {
+                    eprintln!("Could not load memory image.\n{}", e);
+                    false
+                }
+            };
 
-impl fmt::Display for InitError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match *self {
-            InvalidChar(c, loc) =>
-                write!(f, "Invalid character in source program: '{}' \
-                           at location: {:#X}", c, loc),
-            SourceTooShort => write!(f, "Source program is too short."),
-            SourceTooLong => write!(f, "Source program is too long."),
+            if !ok {
+                process::exit(1);
+            }
         }
     }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
-// main
+// Argument Parsing
 ////////////////////////////////////////////////////////////////////////////////
 
-fn main() {
-    let args: Vec<String> = std::env::args().collect();
+/// Where the initial contents of memory come from.
+enum Source {
+    /// Programs to run one after another, each read from a file or from
+    /// stdin if the name is `-`.
+    Programs(Vec<PathBuf>),
+    /// A memory image written by `--dump-memory`.
+    Image(PathBuf),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Dialect {
+    Malbolge,
+    Malbolge20,
+}
 
-    if args.len() != 2 {
-        println!("Usage: {} FILE", args[0]);
-        return;
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+/// Runs Malbolge programs.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// Programs to run one after another, in fresh machines. `-` reads the
+    /// program from stdin, which is the default when stdin is a pipe.
+    #[arg(value_name = "FILE")]
+    files: Vec<PathBuf>,
+    /// Stop after executing N instructions.
+    #[arg(long, value_name = "N")]
+    max_steps: Option<u64>,
+    /// Stop when the program tries to output more than N bytes.
+    #[arg(long, value_name = "N")]
+    max_output: Option<usize>,
+    /// Stop once the program has run for about this long.
+    #[arg(long, value_name = "SECONDS", value_parser = parse_duration)]
+    timeout: Option<Duration>,
+    /// Log every instruction to stderr before executing it.
+    #[arg(long)]
+    trace: bool,
+    /// Color the --trace output: always, never, or auto to color it only on
+    /// a terminal and when NO_COLOR is not set.
+    #[arg(long, value_enum, value_name = "WHEN", default_value = "auto")]
+    color: ColorChoice,
+    /// Pause this many milliseconds before each instruction.
+    #[arg(long, value_name = "MS")]
+    step_delay: Option<u64>,
+    /// Flush the output after every byte the program writes.
+    #[arg(long)]
+    unbuffered: bool,
+    /// Write the contents of memory to PATH once the program halts.
+    #[arg(long, value_name = "PATH")]
+    dump_memory: Option<PathBuf>,
+    /// Show memory cells as ten trits in --dump-memory and the debugger.
+    #[arg(long)]
+    trits: bool,
+    /// Print the opcodes of COUNT cells from START instead of running.
+    #[arg(long, value_name = "START:COUNT", value_parser = parse_range)]
+    disasm: Option<(usize, usize)>,
+    /// Load the program in normalized form, whatever its extension.
+    #[arg(long, conflicts_with = "raw")]
+    normalized: bool,
+    /// Load the program in encrypted form, whatever its extension.
+    #[arg(long)]
+    raw: bool,
+    /// Read the program as pairs of hex digits.
+    #[arg(long)]
+    from_hex: bool,
+    /// Load the program exactly as the C reference interpreter does.
+    #[arg(long, conflicts_with = "normalized")]
+    strict: bool,
+    /// Leave memory past the program zero instead of filling it as the
+    /// specification says. Programs may behave differently.
+    #[arg(long, conflicts_with = "load_image")]
+    no_fill: bool,
+    /// Skip invalid characters in the program instead of failing.
+    #[arg(long, conflicts_with_all = ["normalized", "strict"])]
+    lenient: bool,
+    /// Print the program in normalized form instead of running it.
+    #[arg(long)]
+    to_normalized: bool,
+    /// Start from a memory image written by --dump-memory.
+    #[arg(long, value_name = "PATH", conflicts_with = "files")]
+    load_image: Option<PathBuf>,
+    /// Feed the contents of PATH to the program instead of stdin.
+    #[arg(long, value_name = "PATH")]
+    input: Option<PathBuf>,
+    /// Feed STR to the program instead of stdin.
+    #[arg(long, value_name = "STR", conflicts_with = "input")]
+    stdin_string: Option<String>,
+    /// Give the program each keystroke as soon as it's typed.
+    #[arg(long)]
+    raw_input: bool,
+    /// Write each byte the program reads back to stdout.
+    #[arg(long)]
+    echo: bool,
+    /// Report how many instructions the program executed.
+    #[arg(long)]
+    stats: bool,
+    /// Summarize the registers and memory once the program halts.
+    #[arg(long)]
+    post_mortem: bool,
+    /// Report how much of the program was executed.
+    #[arg(long)]
+    coverage: bool,
+    /// Print a JSON report instead of the program's output.
+    #[arg(long, conflicts_with = "count_only")]
+    json: bool,
+    /// Print only how many instructions were executed.
+    #[arg(long)]
+    count_only: bool,
+    /// Step through the program in an interactive debugger.
+    #[arg(long)]
+    debug: bool,
+    /// Print the program's length and opcode counts instead of running it.
+    #[arg(long)]
+    check: bool,
+    /// Print a histogram of the program's opcodes and their entropy instead
+    /// of running it.
+    #[arg(long)]
+    entropy: bool,
+    /// The language the program is written in.
+    #[arg(long, value_enum, value_name = "NAME", default_value = "malbolge")]
+    dialect: Dialect,
+    /// Go on to the next program after one fails.
+    #[arg(long)]
+    continue_on_error: bool,
+}
+
+struct Options {
+    source: Source,
+    dialect: Dialect,
+    max_steps: Option<u64>,
+    max_output: Option<usize>,
+    timeout: Option<Duration>,
+    trace: bool,
+    trace_color: bool,
+    step_delay: Option<Duration>,
+    unbuffered: bool,
+    dump_memory: Option<PathBuf>,
+    trits: bool,
+    disasm: Option<(usize, usize)>,
+    /// Whether the program is in normalized form, or `None` to decide by
+    /// its file extension.
+    normalized: Option<bool>,
+    from_hex: bool,
+    strict: bool,
+    no_fill: bool,
+    lenient: bool,
+    to_normalized: bool,
+    input: Option<PathBuf>,
+    stdin_string: Option<String>,
+    raw_input: bool,
+    echo: bool,
+    stats: bool,
+    post_mortem: bool,
+    coverage: bool,
+    json: bool,
+    count_only: bool,
+    debug: bool,
+    check: bool,
+    continue_on_error: bool,
+    entropy: bool,
+}
+
+/// Parses the command line, exiting with a usage message if it's invalid.
+fn parse_args() -> Options {
+    let args = Args::parse();
+
+    let source = match (args.files.is_empty(), args.load_image) {
+        (false, _) => Source::Programs(args.files),
+        (true, Some(path)) => Source::Image(path),
+        // read the program from stdin if it's being piped in
+        (true, None) if !io::stdin().is_terminal() => Source::Programs(vec![PathBuf::from("-")]),
+        (true, None) => {
+            Args::command()
+                .error(ErrorKind::MissingRequiredArgument,
+                       "no program given: pass a FILE, pipe one in, or use --load-image")
+                .exit();
+        }
+    };
+
+    Options {
+        source,
+        dialect: args.dialect,
+        max_steps: args.max_steps,
+        max_output: args.max_output,
+        timeout: args.timeout,
+        trace: args.trace,
+        trace_color: use_color(args.color),
+        step_delay: args.step_delay.map(Duration::from_millis),
+        unbuffered: args.unbuffered,
+        dump_memory: args.dump_memory,
+        trits: args.trits,
+        disasm: args.disasm,
+        normalized: match (args.normalized, args.raw) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        },
+        from_hex: args.from_hex,
+        strict: args.strict,
+        no_fill: args.no_fill,
+        lenient: args.lenient,
+        to_normalized: args.to_normalized,
+        input: args.input,
+        stdin_string: args.stdin_string,
+        raw_input: args.raw_input,
+        echo: args.echo,
+        stats: args.stats,
+        post_mortem: args.post_mortem,
+        coverage: args.coverage,
+        json: args.json,
+        count_only: args.count_only,
+        debug: args.debug,
+        check: args.check,
+        continue_on_error: args.continue_on_error,
+        entropy: args.entropy,
     }
+}
 
-    match load(&args[1]) {
-        Ok(contents) => run(contents),
-        Err(e) => println!("{}", e),
+/// Decides whether to color the trace, which goes to stderr. An empty
+/// NO_COLOR doesn't count, as https://no-color.org says.
+fn use_color(when: ColorChoice) -> bool {
+    match when {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => io::stderr().is_terminal()
+            && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()),
     }
 }
 
+/// Parses a number of seconds, which may have a fractional part.
+fn parse_duration(arg: &str) -> Result<Duration, String> {
+    let secs = arg.parse().map_err(|e: std::num::ParseFloatError| e.to_string())?;
+    Duration::try_from_secs_f64(secs).map_err(|e| e.to_string())
+}
+
+/// Parses a `START:COUNT` pair.
+fn parse_range(arg: &str) -> Result<(usize, usize), String> {
+    let (start, count) = arg.split_once(':').ok_or("expected START:COUNT")?;
+    let parse = |n: &str| n.parse::<usize>().map_err(|e| e.to_string());
+    Ok((parse(start)?, parse(count)?))
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // File Handling
 ////////////////////////////////////////////////////////////////////////////////
 
-fn load(filename: &str) -> std::io::Result<Vec<u8>> {
-    let path = Path::new(filename);
-    let mut file = File::open(path)?;
+/// Reads the program from `filename`, or from stdin if `filename` is `-`.
+/// Gzip-compressed programs are decompressed.
+fn load(filename: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+    let path = filename.as_ref();
+    let contents = if path == Path::new("-") {
+        load_from(&mut io::stdin())?
+    } else {
+        let mut file = File::open(path)?;
+        load_from(&mut file)?
+    };
+
+    if contents.starts_with(GZIP_MAGIC) || has_extension(path, "gz") {
+        return gunzip(&contents);
+    }
 
+    Ok(contents)
+}
+
+const GZIP_MAGIC: &[u8] = &[0x1F, 0x8B];
+
+/// Whether `filename` has an extension used for normalized programs,
+/// looking past a `.gz`. Stdin has no extension, so it's read as raw.
+fn is_normalized_name(filename: &Path) -> bool {
+    let filename = if has_extension(filename, "gz") {
+        Path::new(filename.file_stem().unwrap())
+    } else {
+        filename
+    };
+
+    NORMALIZED_EXTENSIONS.iter().any(|ext| has_extension(filename, ext))
+}
+
+fn has_extension(path: &Path, ext: &str) -> bool {
+    path.extension().is_some_and(|e| e == ext)
+}
+
+const NORMALIZED_EXTENSIONS: &[&str] = &["mbn", "nmb"];
+
+#[cfg(feature = "gzip")]
+fn gunzip(contents: &[u8]) -> io::Result<Vec<u8>> {
+    load_from(&mut flate2::read::GzDecoder::new(contents))
+}
+
+#[cfg(not(feature = "gzip"))]
+fn gunzip(_contents: &[u8]) -> io::Result<Vec<u8>> {
+    Err(io::Error::other("Loading gzip-compressed programs requires the gzip feature."))
+}
+
+/// Parses pairs of hex digits, which may be separated by whitespace, into
+/// the bytes they stand for.
+fn from_hex(contents: &[u8]) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(contents.len() / 2);
+    let mut high = None;
+
+    for (offset, &c) in contents.iter().enumerate() {
+        if c.is_ascii_whitespace() {
+            continue;
+        }
+
+        let digit = match (c as char).to_digit(16) {
+            Some(digit) => digit as u8,
+            None => {
+                let why = format!("Invalid hex digit '{}' at offset {}.", c.escape_ascii(), offset);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, why));
+            }
+        };
+
+        match high.take() {
+            Some(high) => bytes.push(high << 4 | digit),
+            None => high = Some(digit),
+        }
+    }
+
+    if high.is_some() {
+        let why = "The hex program has an odd number of digits.";
+        return Err(io::Error::new(io::ErrorKind::InvalidData, why));
+    }
+
+    Ok(bytes)
+}
+
+fn load_from(reader: &mut dyn Read) -> io::Result<Vec<u8>> {
     let mut contents = Vec::new();
-    file.read_to_end(&mut contents)?;
+    reader.read_to_end(&mut contents)?;
 
     Ok(contents)
 }
 
 ////////////////////////////////////////////////////////////////////////////////
-// Interpreter Core
+// Interpreter
 ////////////////////////////////////////////////////////////////////////////////
 
-fn run(contents: Vec<u8>) {
-    let mut mem = [0; MAX_MEMORY];
+fn run(contents: &[u8], filename: &Path, options: &Options) -> bool {
+    if options.to_normalized {
+        return match malbolge::to_normalized(contents) {
+            Ok(normalized) => {
+                println!("{}", normalized);
+                true
+            }
+            Err(why) => {
+                eprintln!("Could not normalize program.\n{}", why);
+                false
+            }
+        };
+    }
+
+    // the C loader only knows the encrypted form
+    let normalized = !options.strict
+        && options.normalized.unwrap_or_else(|| is_normalized_name(filename));
+
+    // clap only catches `--normalized`, not a normalized extension
+    if normalized && options.lenient {
+        eprintln!("--lenient only applies to programs in encrypted form; \
+                  pass --raw to load {} as one.", filename.display());
+        return false;
+    }
+
+    let (mut mem, len) = match init(contents, normalized, options.strict, options.lenient) {
+        Ok(loaded) => loaded,
+        Err(why) if options.json => {
+            println!("{}", Report::error(why.to_string()).to_json());
+            return false;
+        }
+        Err(why) => {
+            eprintln!("Could not initialize memory.\n{}", why);
+            return false;
+        }
+    };
+
+    if options.no_fill {
+        malbolge::fill_memory(&mut mem, len, MemoryFill::Constant(0))
+            .expect("zero is a valid fill value");
+    }
+
+    if options.check {
+        check(&mem, len);
+        return true;
+    }
 
-    match init(contents, &mut mem) {
-        Ok(_) => execute(&mut mem),
-        Err(why) => println!("Could not initialize memory.\n{}", why),
+    if options.entropy {
+        entropy(&mem, len);
+        return true;
     }
+
+    // advisory only: self-modifying code can still reach a `v`
+    if !malbolge::contains_opcode(&mem, len, 'v') {
+        eprintln!("Warning: the program has no v instruction and may not terminate.");
+    }
+
+    run_image(mem, len, options)
 }
 
-fn init(input: Vec<u8>, mem: &mut Memory) -> Result<usize, InitError> {
-    let mut i = 0;
-    let valid = "ji*p</vo";
+/// Opens whatever the program's `/` instruction should read from.
+fn open_input(options: &Options) -> Option<Box<dyn Read + Send>> {
+    if options.count_only {
+        return Some(Box::new(io::empty()));
+    }
 
-    for (loc, &b) in input.iter().enumerate() {
-        if (b as char).is_whitespace() {
-            continue;
+    let mut input: Box<dyn Read + Send> = match options.input {
+        Some(ref path) => match File::open(path) {
+            Ok(file) => Box::new(BufReader::new(file)),
+            Err(e) => {
+                eprintln!("Could not open {}: {}", path.display(), e);
+                return None;
+            }
+        },
+        None => match options.stdin_string {
+            Some(ref s) => Box::new(Cursor::new(s.clone().into_bytes())),
+            None => Box::new(io::stdin()),
+        },
+    };
+
+    if options.raw_input || options.echo {
+        input = Box::new(terminal::Interactive::new(input, options.echo));
+    }
+
+    Some(input)
+}
+
+/// Runs the machine whose memory has been loaded into `mem`, with the
+/// program itself in the first `len` cells.
+fn run_image(mem: Box<Memory>, len: usize, options: &Options) -> bool {
+    if let Some((start, count)) = options.disasm {
+        for (addr, op) in malbolge::disassemble(&mem, start, count) {
+            println!("{:>5}  {}", addr, op);
         }
 
-        if is_printable(b as usize) {
-            let index = (b as usize - 33 + i) % 94;
-            let test = XLAT1[index] as char;
+        return true;
+    }
+
+    let mut input = match open_input(options) {
+        Some(input) => input,
+        None => return false,
+    };
+
+    let mut builder = InterpreterBuilder::new()
+        .trace(options.trace)
+        .trace_color(options.trace_color)
+        .coverage(options.coverage)
+        .flush_policy(flush_policy(options));
+
+    if let Some(max) = options.max_steps {
+        builder = builder.max_steps(max);
+    }
+
+    if let Some(max) = options.max_output {
+        builder = builder.max_output(max);
+    }
+
+    if let Some(max) = options.timeout {
+        builder = builder.max_duration(max);
+    }
+
+    if let Some(delay) = options.step_delay {
+        builder = builder.step_delay(delay);
+    }
+
+    if options.debug {
+        let mut interpreter = builder.build_image(mem);
+        debugger::run(&mut interpreter, &mut input, options.trits);
+        return true;
+    }
+
+    // any printable cell of the program could be an instruction
+    let instructions: Vec<usize> = (0..len)
+        .filter(|&addr| malbolge::is_printable(mem[addr] as usize))
+        .collect();
+
+    let raw_mode = raw_mode(options);
+
+    let mut interpreter;
+    let result;
+    let ok = if options.json {
+        interpreter = builder.build_image(mem);
+        let mut output = Vec::new();
+        result = interpreter.execute(&mut input, &mut output);
+        json_report(&result, interpreter.instruction_count(), &output)
+    } else if options.count_only {
+        interpreter = builder.build_image(mem);
+        result = interpreter.execute(&mut input, &mut io::sink());
+        count_report(&result, interpreter.instruction_count())
+    } else {
+        interpreter = builder.build_image(mem);
+        result = run_on_stdout(&mut input, |input, output| interpreter.execute(input, output));
+        report(&result)
+    };
+    drop(raw_mode);
+
+    if options.post_mortem {
+        post_mortem(&interpreter, &result);
+    }
+
+    if options.stats {
+        eprintln!("Instructions executed: {}", interpreter.instruction_count());
+    }
 
-            if !valid.contains(&test.to_string()) {
-                return Err(InvalidChar(b as char, loc));
+    if options.coverage {
+        let covered = instructions.iter()
+            .filter(|&&addr| interpreter.coverage().contains(addr))
+            .count();
+        let percent = 100.0 * covered as f64 / instructions.len().max(1) as f64;
+        eprintln!("Coverage: {} of {} instructions executed ({:.1}%), {} addresses in all",
+                  covered, instructions.len(), percent, interpreter.coverage().len());
+    }
+
+    if let Some(ref path) = options.dump_memory {
+        let result = File::create(path).and_then(|file| {
+            let mut out = BufWriter::new(file);
+            if options.trits {
+                dump_trits(&interpreter, &mut out)?;
+            } else {
+                interpreter.dump_memory(&mut out)?;
             }
-        }
+            out.flush()
+        });
 
-        if i >= MAX_MEMORY {
-            return Err(SourceTooLong);
+        if let Err(e) = result {
+            eprintln!("Could not dump memory to {}: {}", path.display(), e);
+            return false;
         }
+    }
+
+    ok
+}
+
+/// How often the program's output is flushed. Buffered output still shows
+/// a prompt before the program reads.
+fn flush_policy(options: &Options) -> FlushPolicy {
+    if options.unbuffered { FlushPolicy::EveryByte } else { FlushPolicy::BeforeInput }
+}
 
-        mem[i] = b as usize;
-        i += 1;
+/// Switches the terminal to raw mode for `--raw-input`, if the program
+/// reads from it. Raw mode lasts until the result is dropped.
+fn raw_mode(options: &Options) -> Option<terminal::RawMode> {
+    // only a terminal on stdin can be switched to raw mode
+    if !options.raw_input || options.input.is_some() || options.stdin_string.is_some()
+        || !io::stdin().is_terminal() {
+        return None;
     }
 
-    if i < 2 {
-        // the C version does not check for this case
-        return Err(SourceTooShort);
+    let raw_mode = terminal::RawMode::enable();
+    if raw_mode.is_none() {
+        eprintln!("Warning: could not switch the terminal to raw mode.");
     }
+    raw_mode
+}
+
+/// Runs a program with `execute`, which is handed `input` and a buffered
+/// stdout that is flushed once the program halts.
+fn run_on_stdout<F>(input: &mut dyn Read, execute: F) -> io::Result<Exit>
+    where F: FnOnce(&mut dyn Read, &mut dyn Write) -> io::Result<Exit>
+{
+    let mut output = BufWriter::new(io::stdout());
 
-    // fill in the rest of memory
-    for n in i..MAX_MEMORY {
-        mem[n] = crazy_op(mem[n - 1], mem[n - 2]);
+    execute(input, &mut output).and_then(|exit| output.flush().map(|_| exit))
+}
+
+/// Writes the contents of memory like `dump_memory`, but with each value
+/// as ten trits.
+fn dump_trits(interpreter: &Interpreter, out: &mut dyn Write) -> io::Result<()> {
+    for (addr, &value) in interpreter.mem_view().iter().enumerate() {
+        writeln!(out, "{:>5} {}", addr, Trits(value as usize))?;
     }
 
-    Ok(MAX_MEMORY)
+    Ok(())
 }
 
-fn execute(mem: &mut Memory) {
-    let mut r_a = 0;
-    let mut r_c = 0;
-    let mut r_d = 0;
-    let mut input = std::io::stdin();
+/// Prints why the program stopped, unless it halted normally. Returns
+/// whether it ran without an I/O error.
+fn report(result: &io::Result<Exit>) -> bool {
+    match result {
+        Ok(exit) if exit.halt == Halt::Stopped => {}
+        Ok(exit) => eprintln!("{}", exit.halt),
+        Err(e) => {
+            eprintln!("{}", e);
+            return false;
+        }
+    }
 
-    while is_printable(mem[r_c]) {
-        let index = (mem[r_c] - 33 + r_c) % 94;
-        let op = XLAT1[index] as char;
+    true
+}
 
-        match op {
-            'j' => r_d = mem[r_d],
-            'i' => r_c = mem[r_d],
-            '*' => {
-                r_a = tri_rotate(mem[r_d]);
-                mem[r_d] = r_a;
-            }
-            'p' => {
-                r_a = crazy_op(r_a, mem[r_d]);
-                mem[r_d] = r_a;
-            }
-            '<' => print!("{}", r_a as u8 as char),
-            '/' => {
-                let mut buf = [0u8];
-                let result = input.read(&mut buf);
-
-                match result {
-                    Ok(cnt) => {
-                        if cnt == 1 {
-                            // read a byte
-                            r_a = buf[0] as usize;
-                        } else if cnt == 0 {
-                            // EOF
-                            r_a = MAX_MEMORY - 1;
-                        }
-                    }
-                    Err(e) => println!("{}", e),
-                }
+/// Prints the `--count-only` summary of a run. Returns whether it ran
+/// without an I/O error.
+fn count_report(result: &io::Result<Exit>, instructions: u64) -> bool {
+    match result {
+        Ok(exit) => {
+            println!("Instructions executed: {}. {}", instructions, exit.halt);
+            true
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            false
+        }
+    }
+}
+
+/// Prints the `--json` summary of a run in place of the program's output.
+/// Returns whether it ran without an I/O error.
+fn json_report(result: &io::Result<Exit>, instructions: u64, output: &[u8]) -> bool {
+    let ok = result.is_ok();
+    let report = match result {
+        Ok(exit) => Report { exit: Some(*exit), instructions, output, error: None },
+        Err(e) => Report { exit: None, instructions, output, error: Some(e.to_string()) },
+    };
+
+    println!("{}", report.to_json());
+    ok
+}
+
+/// How many cells either side of `r_c` the `--post-mortem` summary lists.
+const POST_MORTEM_WINDOW: usize = 4;
+
+/// Prints the `--post-mortem` summary of a run to stderr: why it stopped,
+/// the final registers, how much it did and the memory around `r_c`.
+fn post_mortem(interpreter: &Interpreter, result: &io::Result<Exit>) {
+    let (r_a, r_c, r_d) = interpreter.registers();
+
+    eprintln!("==> post-mortem <==");
+    match result {
+        Ok(exit) => eprintln!("Halt: {}", exit.halt),
+        Err(e) => eprintln!("Error: {}", e),
+    }
+    eprintln!("Registers: r_a = {}, r_c = {}, r_d = {}", r_a, r_c, r_d);
+    eprintln!("Instructions executed: {}", interpreter.instruction_count());
+    eprintln!("Bytes output: {}", interpreter.output_count());
+
+    let range = r_c.saturating_sub(POST_MORTEM_WINDOW)..(r_c + POST_MORTEM_WINDOW + 1).min(MAX_MEMORY);
+    for (addr, line) in range.clone().zip(interpreter.listing(range).lines()) {
+        let marker = if addr == r_c { "=>" } else { "  " };
+        eprintln!("{} {}", marker, line);
+    }
+}
+
+/// Loads the program into memory, returning the memory and the length of
+/// the program.
+fn init(contents: &[u8], normalized: bool, strict: bool, lenient: bool) -> Result<(Box<Memory>, usize), InitError> {
+    let mut mem: Box<Memory> = vec![0; MAX_MEMORY].try_into().unwrap();
+    let len = if normalized {
+        malbolge::init_normalized(contents, &mut mem)?
+    } else if strict {
+        malbolge::init_strict(contents, &mut mem)?
+    } else if lenient {
+        let (len, skipped) = malbolge::init_lenient(contents, &mut mem)?;
+
+        for e in skipped {
+            if let InitError::InvalidChar { c, line, column, .. } = e {
+                eprintln!("Warning: skipped invalid character '{}' at line {}, column {}.", c, line, column);
             }
-            'v' => return,
-            _ => { /* no op */ }
         }
 
-        let index = mem[r_c] - 33;
-        mem[r_c] = XLAT2[index] as usize;
-        r_c = (r_c + 1) % MAX_MEMORY;
-        r_d = (r_d + 1) % MAX_MEMORY;
+        len
+    } else {
+        malbolge::init(contents, &mut mem)?
+    };
+
+    Ok((mem, len))
+}
+
+/// Prints some statistics about a program of length `len` that has been
+/// loaded into `mem`.
+fn check(mem: &Memory, len: usize) {
+    let histogram = malbolge::opcode_histogram(mem, len);
+    let data = len - histogram.iter().sum::<usize>();
+
+    println!("Length: {} instructions", len);
+
+    for (op, count) in OPCODES.chars().zip(histogram) {
+        println!("  {}: {}", op, count);
+    }
+
+    if data > 0 {
+        println!("  data: {}", data);
     }
+
+    let ends_in_v = len > 0 && malbolge::disassemble(mem, len - 1, 1)[0].1 == 'v';
+    println!("Ends in v: {}", if ends_in_v { "yes" } else { "no" });
+
+    if !malbolge::contains_opcode(mem, len, 'v') {
+        println!("Warning: the program has no v instruction and may not terminate.");
+    }
+}
+
+/// Prints a histogram of the instructions in a program of length `len`
+/// that has been loaded into `mem`, along with its entropy.
+fn entropy(mem: &Memory, len: usize) {
+    const BAR_WIDTH: f64 = 50.0;
+
+    let histogram = malbolge::opcode_histogram(mem, len);
+    let data = len - histogram.iter().sum::<usize>();
+    let mut counts: Vec<(String, usize)> = OPCODES.chars()
+        .map(|op| op.to_string())
+        .zip(histogram)
+        .collect();
+
+    if data > 0 {
+        counts.push(("data".to_string(), data));
+    }
+
+    let mut bits = 0.0;
+
+    for (name, count) in &counts {
+        let p = *count as f64 / len as f64;
+        let bar = "#".repeat((p * BAR_WIDTH).round() as usize);
+        eprintln!("{:>4} {:>6} {:>6.2}%  {}", name, count, p * 100.0, bar);
+
+        if *count > 0 {
+            bits -= p * p.log2();
+        }
+    }
+
+    eprintln!("Entropy: {:.3} bits per cell", bits);
 }
 
 ////////////////////////////////////////////////////////////////////////////////
-// Interpreter Functions
+// Malbolge20
 ////////////////////////////////////////////////////////////////////////////////
 
-#[inline]
-fn is_printable(c: usize) -> bool {
-    32 < c && c < 127
+fn supports_malbolge20(options: &Options) -> bool {
+    matches!(options.source, Source::Programs(_))
+        && options.timeout.is_none()
+        && !options.trace
+        && options.step_delay.is_none()
+        && options.dump_memory.is_none()
+        && options.disasm.is_none()
+        && options.normalized != Some(true)
+        && !options.strict
+        && !options.no_fill
+        && !options.lenient
+        && !options.to_normalized
+        && !options.stats
+        && !options.post_mortem
+        && !options.coverage
+        && !options.json
+        && !options.count_only
+        && !options.debug
+        && !options.check
+        && !options.entropy
+        && !options.trits
 }
 
-#[inline]
-fn tri_rotate(x: usize) -> usize {
-    // shift right and move the rightmost trit to the front
-    let (q, r) = (x / 3, x % 3);
-    q + r * 19683 // 3^9 == 19683
-}
+fn run20(contents: &[u8], options: &Options) -> bool {
+    let config = Config {
+        max_steps: options.max_steps,
+        max_output: options.max_output,
+        eof_value: malbolge20::EOF_VALUE,
+        flush_policy: flush_policy(options),
+        ..Config::default()
+    };
+
+    let mut interpreter = match malbolge20::Interpreter::with_config(contents, config) {
+        Ok(interpreter) => interpreter,
+        Err(why) => {
+            eprintln!("Could not initialize memory.\n{}", why);
+            return false;
+        }
+    };
+
+    let mut input = match open_input(options) {
+        Some(input) => input,
+        None => return false,
+    };
 
-#[inline]
-fn crazy_op(x: usize, y: usize) -> usize {
-    static P9: [usize; 5] = [1, 9, 81, 729, 6561];
-    static O: [[usize; 9]; 9] = [
-        [4, 3, 3, 1, 0, 0, 1, 0, 0],
-        [4, 3, 5, 1, 0, 2, 1, 0, 2],
-        [5, 5, 4, 2, 2, 1, 2, 2, 1],
-        [4, 3, 3, 1, 0, 0, 7, 6, 6],
-        [4, 3, 5, 1, 0, 2, 7, 6, 8],
-        [5, 5, 4, 2, 2, 1, 8, 8, 7],
-        [7, 6, 6, 7, 6, 6, 4, 3, 3],
-        [7, 6, 8, 7, 6, 8, 4, 3, 5],
-        [8, 8, 7, 8, 8, 7, 5, 5, 4],
-    ];
+    let raw_mode = raw_mode(options);
+    let result = run_on_stdout(&mut input, |input, output| interpreter.execute(input, output));
+    drop(raw_mode);
 
-    (0..5).fold(0, |sum, i| sum + O[y / P9[i] % 9][x / P9[i] % 9] * P9[i])
+    report(&result)
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -246,12 +905,21 @@ fn crazy_op(x: usize, y: usize) -> usize {
 
 #[cfg(test)]
 mod tests {
-    use super::tri_rotate;
+    use super::*;
 
     #[test]
-    fn rotate_test() {
-        let input = 17;
-        let rotated = (0..10).fold(input, |prev, _| tri_rotate(prev));
-        assert_eq!(input, rotated);
+    fn args_test() {
+        Args::command().debug_assert();
+
+        let args = Args::try_parse_from(["malbolge", "programs/hello-world.mb"]).unwrap();
+        assert_eq!(args.files, [PathBuf::from("programs/hello-world.mb")]);
+        assert!(args.dialect == Dialect::Malbolge && args.max_steps.is_none());
+
+        let args = Args::try_parse_from(["malbolge", "--disasm", "3:4", "--timeout", "0.5", "x"]).unwrap();
+        assert_eq!(args.disasm, Some((3, 4)));
+        assert_eq!(args.timeout, Some(Duration::from_millis(500)));
+
+        assert!(Args::try_parse_from(["malbolge", "--raw", "--normalized", "x"]).is_err());
+        assert!(Args::try_parse_from(["malbolge", "--input", "a", "--stdin-string", "b", "x"]).is_err());
     }
 }
@@ -0,0 +1,438 @@
+// MIT License
+//
+// Copyright (C) 2015-2018 Steve Sprang
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Malbolge20, the dialect with 20-trit words and 3^20 memory cells.
+//!
+//! Everything except the word size works as in Malbolge. Memory is far too
+//! big to allocate up front, so only the program and the start of the
+//! crazy operation fill are stored. The fill repeats after a few cells, so
+//! any other cell can be worked out from its address until it's written.
+
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+
+use crate::{decode, invalid_char, is_printable, is_space, program_bytes, read_byte};
+use crate::{Config, Exit, FlushPolicy, Halt, InitError, OutputMode, OPCODES, XLAT2};
+use crate::InitError::*;
+
+/// The number of memory cells: 3^20.
+pub const MAX_MEMORY: usize = 3_486_784_401;
+
+/// The value the `/` instruction stores in `r_a` at the end of input.
+pub const EOF_VALUE: usize = MAX_MEMORY - 1;
+
+/// 3^10, the size of a Malbolge word.
+const HALF: usize = crate::MAX_MEMORY;
+
+////////////////////////////////////////////////////////////////////////////////
+// Memory
+////////////////////////////////////////////////////////////////////////////////
+
+/// The memory of a Malbolge20 machine.
+#[derive(Debug, Clone)]
+pub struct Memory {
+    /// The program followed by the fill up to the end of its first cycle.
+    /// Only the program's own cells are ever written here, since the
+    /// cycle stands in for every later copy of it.
+    cells: Vec<u32>,
+    /// The length of the program.
+    len: usize,
+    /// Where the fill starts repeating, and how often.
+    cycle_start: usize,
+    period: usize,
+    /// Cells past the end of the program that have been written.
+    written: HashMap<usize, u32>,
+}
+
+impl Memory {
+    /// Loads `input` following the same rules as `crate::init`.
+    pub fn new(input: &[u8]) -> Result<Memory, InitError> {
+        let mut cells = Vec::new();
+
+        for (loc, b) in program_bytes(input) {
+            if is_space(b) {
+                continue;
+            }
+
+            if cells.len() >= MAX_MEMORY {
+                return Err(SourceTooLong);
+            }
+
+            if is_printable(b as usize) && !OPCODES.contains(decode(b as usize, cells.len())) {
+                return Err(invalid_char(input, loc, cells.len()));
+            }
+
+            cells.push(b as u32);
+        }
+
+        if cells.is_empty() {
+            return Err(SourceEmpty);
+        }
+
+        if cells.len() < 2 {
+            return Err(SourceTooShort);
+        }
+
+        // each fill value depends only on the two before it, so the fill
+        // repeats as soon as a pair of neighbours does
+        let mut seen = HashMap::new();
+        let len = cells.len();
+        let mut n = len;
+
+        let cycle_start = loop {
+            let pair = (cells[n - 2], cells[n - 1]);
+
+            if let Some(&start) = seen.get(&pair) {
+                break start;
+            }
+
+            seen.insert(pair, n);
+
+            if n == MAX_MEMORY {
+                break n;
+            }
+
+            cells.push(crazy_op(pair.1 as usize, pair.0 as usize) as u32);
+            n += 1;
+        };
+
+        // cells[cycle_start..n] repeats forever
+        let period = n - cycle_start;
+
+        Ok(Memory { cells, len, cycle_start, period, written: HashMap::new() })
+    }
+
+    /// Returns the value of the cell at `addr`.
+    pub fn get(&self, addr: usize) -> usize {
+        debug_assert!(addr < MAX_MEMORY);
+
+        if addr < self.len {
+            return self.cells[addr] as usize;
+        }
+
+        match self.written.get(&addr) {
+            Some(&value) => value as usize,
+            None if addr < self.cells.len() => self.cells[addr] as usize,
+            None => {
+                let offset = (addr - self.cycle_start) % self.period;
+                self.cells[self.cycle_start + offset] as usize
+            }
+        }
+    }
+
+    fn set(&mut self, addr: usize, value: usize) {
+        debug_assert!(value < MAX_MEMORY);
+
+        if addr < self.len {
+            self.cells[addr] = value as u32;
+        } else {
+            self.written.insert(addr, value as u32);
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Interpreter
+////////////////////////////////////////////////////////////////////////////////
+
+/// A Malbolge20 virtual machine.
+pub struct Interpreter {
+    mem: Memory,
+    r_a: usize,
+    r_c: usize,
+    r_d: usize,
+    config: Config,
+}
+
+impl Interpreter {
+    /// Loads `source` into a fresh machine with all registers set to zero.
+    pub fn new(source: &[u8]) -> Result<Interpreter, InitError> {
+        let config = Config { eof_value: EOF_VALUE, ..Config::default() };
+        Interpreter::with_config(source, config)
+    }
+
+    /// Like `new`, but with options. `max_steps`, `max_output`,
+    /// `eof_value`, `output_mode` and `flush_policy` are supported;
+    /// `eof_value` must be less than this module's `MAX_MEMORY`. The rest
+    /// are ignored.
+    pub fn with_config(source: &[u8], config: Config) -> Result<Interpreter, InitError> {
+        let mem = Memory::new(source)?;
+        Ok(Interpreter { mem, r_a: 0, r_c: 0, r_d: 0, config })
+    }
+
+    /// Returns the value of the memory cell at `addr`.
+    pub fn mem(&self, addr: usize) -> usize {
+        self.mem.get(addr)
+    }
+
+    /// Runs the program until it halts, like `crate::Interpreter::execute`.
+    /// Only the `Config` fields listed at `with_config` apply, so there is
+    /// no tracing or loop detection, and an error reading
+    /// `input` other than `ErrorKind::Interrupted`, which is retried, is
+    /// returned with the machine still at the `/`.
+    pub fn execute(&mut self,
+                   input: &mut dyn Read,
+                   output: &mut dyn Write) -> io::Result<Exit> {
+        let mut steps = 0;
+        let mut written = 0;
+
+        loop {
+            if self.config.max_steps.is_some_and(|max| steps >= max) {
+                return Ok(self.exit(Halt::StepLimitReached));
+            }
+
+            let value = self.mem.get(self.r_c);
+
+            if !is_printable(value) {
+                return Ok(self.exit(Halt::FellThrough { addr: self.r_c, value }));
+            }
+
+            steps += 1;
+
+            match decode(value, self.r_c) {
+                'j' => self.r_d = self.mem.get(self.r_d),
+                'i' => self.r_c = self.mem.get(self.r_d),
+                '*' => {
+                    self.r_a = tri_rotate(self.mem.get(self.r_d));
+                    self.mem.set(self.r_d, self.r_a);
+                }
+                'p' => {
+                    self.r_a = crazy_op(self.r_a, self.mem.get(self.r_d));
+                    self.mem.set(self.r_d, self.r_a);
+                }
+                '<' => {
+                    if self.config.max_output.is_some_and(|max| written >= max) {
+                        return Ok(self.exit(Halt::OutputLimitReached));
+                    }
+
+                    written += 1;
+
+                    let b = self.r_a as u8;
+                    match self.config.output_mode {
+                        OutputMode::Raw => output.write_all(&[b])?,
+                        OutputMode::Utf8Lossy => write!(output, "{}", b as char)?,
+                    }
+
+                    if self.config.flush_policy == FlushPolicy::EveryByte {
+                        output.flush()?;
+                    }
+                }
+                '/' => {
+                    if self.config.flush_policy != FlushPolicy::Never {
+                        output.flush()?;
+                    }
+
+                    self.r_a = match read_byte(input)? {
+                        Some(b) => b as usize,
+                        None => self.config.eof_value,
+                    };
+                }
+                'v' => return Ok(self.exit(Halt::Stopped)),
+                _ => {} // no op
+            }
+
+            // as in Malbolge, a jump may land on a cell that can't be
+            // re-encrypted; leave it for the next step to report
+            let value = self.mem.get(self.r_c);
+
+            if is_printable(value) {
+                self.mem.set(self.r_c, XLAT2[value - 33] as usize);
+            }
+
+            self.r_c = next_addr(self.r_c);
+            self.r_d = next_addr(self.r_d);
+        }
+    }
+
+    fn exit(&self, halt: Halt) -> Exit {
+        Exit { halt, r_a: self.r_a, r_c: self.r_c, r_d: self.r_d }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Interpreter Functions
+////////////////////////////////////////////////////////////////////////////////
+
+#[inline]
+fn next_addr(addr: usize) -> usize {
+    if addr == MAX_MEMORY - 1 { 0 } else { addr + 1 }
+}
+
+/// `crate::tri_rotate` for 20-trit words.
+#[inline]
+pub fn tri_rotate(x: usize) -> usize {
+    debug_assert!(x < MAX_MEMORY);
+
+    let (q, r) = (x / 3, x % 3);
+    q + r * (MAX_MEMORY / 3) // 3^19
+}
+
+/// `crate::crazy_op` for 20-trit words. The operation works on each trit
+/// separately, so it can be applied to the two halves of each word.
+#[inline]
+pub fn crazy_op(x: usize, y: usize) -> usize {
+    debug_assert!(x < MAX_MEMORY && y < MAX_MEMORY);
+
+    let low = crate::crazy_op(x % HALF, y % HALF);
+    let high = crate::crazy_op(x / HALF, y / HALF);
+    high * HALF + low
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Tests
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::tests::{random_cells, reference_crazy_op};
+
+    #[test]
+    fn rotate_test() {
+        for x in [0, 1, 2, 17, HALF, MAX_MEMORY - 1] {
+            let rotated = (0..20).fold(x, |prev, _| tri_rotate(prev));
+            assert_eq!(rotated, x);
+        }
+
+        assert_eq!(tri_rotate(1), 3usize.pow(19));
+    }
+
+    #[test]
+    fn crazy_op_test() {
+        for x in (0..MAX_MEMORY).step_by(123_456_789) {
+            for y in (0..MAX_MEMORY).step_by(98_765_431) {
+                assert_eq!(crazy_op(x, y), reference_crazy_op(x, y, 20));
+            }
+        }
+
+        let mut next = random_cells(0x9E37_79B9_7F4A_7C15, MAX_MEMORY);
+
+        for _ in 0..10_000 {
+            let (x, y) = (next(), next());
+            assert_eq!(crazy_op(x, y), reference_crazy_op(x, y, 20), "crazy_op({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn fill_test() {
+        let mem = Memory::new(b"(=a`").unwrap();
+
+        // work the fill out the slow way
+        let mut cells = vec![40, 61, 97, 96];
+        for n in 4..10_000 {
+            cells.push(crazy_op(cells[n - 1], cells[n - 2]));
+        }
+
+        for (addr, &value) in cells.iter().enumerate() {
+            assert_eq!(mem.get(addr), value);
+        }
+
+        let last = MAX_MEMORY - 1;
+        assert_eq!(mem.get(last), mem.get(last - mem.period));
+    }
+
+    #[test]
+    fn fill_write_test() {
+        let mut mem = Memory::new(b"(=a`").unwrap();
+
+        // a cell in the stored cycle and a far off copy of it
+        let addr = mem.cycle_start;
+        let copy = addr + 1000 * mem.period;
+        let value = mem.get(addr);
+        assert_eq!(mem.get(copy), value);
+
+        mem.set(addr, value + 1);
+        assert_eq!(mem.get(addr), value + 1);
+        assert_eq!(mem.get(copy), value);
+        assert_eq!(mem.get(addr + mem.period), value);
+
+        mem.set(copy, 7);
+        assert_eq!(mem.get(copy), 7);
+        assert_eq!(mem.get(addr), value + 1);
+    }
+
+    #[test]
+    fn io_test() {
+        let source = assemble(&["in", "out", "in", "hlt"]).unwrap();
+        let mut interpreter = Interpreter::new(&source).unwrap();
+        let mut output = Vec::new();
+
+        let exit = interpreter.execute(&mut &[200u8][..], &mut output).unwrap();
+        assert_eq!(output, [200]);
+        assert_eq!(exit.halt, Halt::Stopped);
+        assert_eq!(exit.r_a, EOF_VALUE);
+
+        /// Input that is interrupted once before each byte.
+        struct Interrupted(bool, &'static [u8]);
+
+        impl Read for Interrupted {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.0 = !self.0;
+
+                if self.0 {
+                    return Err(io::ErrorKind::Interrupted.into());
+                }
+
+                self.1.read(buf)
+            }
+        }
+
+        let mut interpreter = Interpreter::new(&source).unwrap();
+        let mut output = Vec::new();
+
+        let exit = interpreter.execute(&mut Interrupted(false, b"x"), &mut output).unwrap();
+        assert_eq!(output, b"x");
+        assert_eq!(exit.halt, Halt::Stopped);
+        assert_eq!(exit.r_a, EOF_VALUE);
+
+        /// Records how many bytes had been written at each flush.
+        #[derive(Default)]
+        struct Flushes(Vec<u8>, Vec<usize>);
+
+        impl Write for Flushes {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                self.1.push(self.0.len());
+                Ok(())
+            }
+        }
+
+        let config = Config { eof_value: EOF_VALUE, flush_policy: FlushPolicy::EveryByte, ..Config::default() };
+        let mut interpreter = Interpreter::with_config(&source, config).unwrap();
+        let mut output = Flushes::default();
+
+        interpreter.execute(&mut &b"x"[..], &mut output).unwrap();
+        assert_eq!(output.1, [0, 1, 1]);
+    }
+
+    #[test]
+    fn init_error_test() {
+        assert!(matches!(Interpreter::new(b"("), Err(SourceTooShort)));
+        assert!(matches!(Interpreter::new(b"(=a`x"), Err(InvalidChar { c: 'x', offset: 4, .. })));
+    }
+}
@@ -0,0 +1,113 @@
+// MIT License
+//
+// Copyright (C) 2015-2018 Steve Sprang
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Delta debugging for Malbolge programs.
+
+use alloc::vec::Vec;
+
+use crate::{init, Interpreter};
+
+/// Removes as much of `source` as it can while `oracle` still accepts the
+/// result, and returns what's left. Every candidate must also load with
+/// `init`, since removing a byte moves everything after it to a new
+/// position.
+///
+/// This is the ddmin algorithm: it tries removing each of `n` equal chunks,
+/// starting with halves, and splits into smaller chunks whenever no removal
+/// works, until no single byte can be removed. If `source` itself doesn't
+/// load or isn't accepted, it's returned unchanged.
+pub fn minimize(source: &[u8], oracle: impl Fn(&[u8]) -> bool) -> Vec<u8> {
+    let mut mem = Interpreter::alloc();
+    let mut accepts = |candidate: &[u8]| init(candidate, &mut mem).is_ok() && oracle(candidate);
+
+    let mut current = source.to_vec();
+
+    if !accepts(&current) {
+        return current;
+    }
+
+    let mut n = 2;
+
+    while current.len() >= 2 {
+        let chunk = current.len().div_ceil(n);
+        let mut reduced = false;
+
+        for start in (0..current.len()).step_by(chunk) {
+            let end = (start + chunk).min(current.len());
+            let candidate = [&current[..start], &current[end..]].concat();
+
+            if accepts(&candidate) {
+                current = candidate;
+                n = (n - 1).max(2);
+                reduced = true;
+                break;
+            }
+        }
+
+        if !reduced {
+            // every single byte has been tried
+            if n >= current.len() {
+                break;
+            }
+
+            n = (n * 2).min(current.len());
+        }
+    }
+
+    current
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{encode, Config};
+
+    fn output(source: &[u8]) -> Vec<u8> {
+        let config = Config { max_steps: Some(10_000), ..Config::default() };
+        let mut interpreter = Interpreter::with_config(source, config).unwrap();
+        let mut output = Vec::new();
+        interpreter.execute(&mut std::io::empty(), &mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn minimize_test() {
+        let mut source = include_bytes!("../tests/fixtures/hello-world.mb").to_vec();
+        source.retain(|b| !b.is_ascii_whitespace());
+        let len = source.len();
+
+        // nops that never run
+        for addr in len..len + 40 {
+            source.push(encode('o', addr).unwrap());
+        }
+
+        let minimized = minimize(&source, |candidate| output(candidate) == b"Hello World!");
+        assert!(minimized.len() <= len);
+        assert_eq!(output(&minimized), b"Hello World!");
+
+        // nothing to do when the oracle rejects the program
+        assert_eq!(minimize(&source, |_| false), source);
+        assert_eq!(minimize(b"(", |_| true), b"(");
+    }
+}
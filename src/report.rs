@@ -0,0 +1,155 @@
+// MIT License
+//
+// Copyright (C) 2015-2018 Steve Sprang
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! The machine-readable summary behind the `--json` option.
+
+use std::fmt::Write;
+
+use malbolge::{Exit, Halt};
+
+/// What happened when one program was run.
+pub struct Report<'a> {
+    /// `None` if the program never ran to a halt.
+    pub exit: Option<Exit>,
+    pub instructions: u64,
+    pub output: &'a [u8],
+    pub error: Option<String>,
+}
+
+impl Report<'_> {
+    /// A report for a program that couldn't be loaded or run.
+    pub fn error(error: String) -> Report<'static> {
+        Report { exit: None, instructions: 0, output: &[], error: Some(error) }
+    }
+
+    /// Formats the report as a single-line JSON object. The output is
+    /// base64-encoded since programs may write any byte.
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{");
+
+        match self.exit {
+            Some(exit) => {
+                write!(json, "\"halt_reason\":\"{}\",", halt_reason(exit.halt)).unwrap();
+                write!(json, "\"instructions_executed\":{},", self.instructions).unwrap();
+                write!(json, "\"output_bytes\":\"{}\",", base64(self.output)).unwrap();
+                write!(json, "\"final_registers\":{{\"r_a\":{},\"r_c\":{},\"r_d\":{}}},",
+                       exit.r_a, exit.r_c, exit.r_d).unwrap();
+            }
+            None => {
+                json.push_str("\"halt_reason\":null,");
+                write!(json, "\"instructions_executed\":{},", self.instructions).unwrap();
+                write!(json, "\"output_bytes\":\"{}\",", base64(self.output)).unwrap();
+                json.push_str("\"final_registers\":null,");
+            }
+        }
+
+        match self.error {
+            Some(ref error) => write!(json, "\"error\":\"{}\"}}", escape(error)).unwrap(),
+            None => json.push_str("\"error\":null}"),
+        }
+
+        json
+    }
+}
+
+fn halt_reason(halt: Halt) -> &'static str {
+    match halt {
+        Halt::Stopped => "stopped",
+        Halt::FellThrough { .. } => "fell_through",
+        Halt::StepLimitReached => "step_limit_reached",
+        Halt::OutputLimitReached => "output_limit_reached",
+        Halt::Timeout => "timeout",
+        Halt::Breakpoint(_) => "breakpoint",
+        Halt::LoopDetected => "loop_detected",
+        Halt::InputPending => "input_pending",
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Standard base64 with padding.
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+/// Escapes `s` for use inside a JSON string.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if (c as u32) < 0x20 => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_test() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"H"), "SA==");
+        assert_eq!(base64(b"He"), "SGU=");
+        assert_eq!(base64(b"Hel"), "SGVs");
+        assert_eq!(base64(b"Hello World!"), "SGVsbG8gV29ybGQh");
+        assert_eq!(base64(&[0xFF, 0x00, 0xFA]), "/wD6");
+    }
+
+    #[test]
+    fn to_json_test() {
+        let exit = Exit { halt: Halt::Stopped, r_a: 1, r_c: 2, r_d: 3 };
+        let report = Report { exit: Some(exit), instructions: 7, output: b"Hi", error: None };
+        assert_eq!(report.to_json(),
+                   "{\"halt_reason\":\"stopped\",\"instructions_executed\":7,\
+                    \"output_bytes\":\"SGk=\",\
+                    \"final_registers\":{\"r_a\":1,\"r_c\":2,\"r_d\":3},\"error\":null}");
+
+        let report = Report::error("Bad \"char\"\nhere".to_string());
+        assert_eq!(report.to_json(),
+                   "{\"halt_reason\":null,\"instructions_executed\":0,\"output_bytes\":\"\",\
+                    \"final_registers\":null,\"error\":\"Bad \\\"char\\\"\\nhere\"}");
+    }
+}
@@ -0,0 +1,143 @@
+// MIT License
+//
+// Copyright (C) 2015-2018 Steve Sprang
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Serde support for `State`, built with the `serde` feature.
+//!
+//! Memory is serialized as a flat sequence of cell values. Deserialization
+//! checks that there are exactly `MAX_MEMORY` cells and that every value
+//! is in range, so a restored state can always be executed.
+
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+use crate::{Memory, State, MAX_MEMORY};
+
+#[derive(serde::Serialize)]
+#[serde(rename = "State")]
+struct StateRef<'a> {
+    r_a: usize,
+    r_c: usize,
+    r_d: usize,
+    mem: &'a [u16],
+    stopped: bool,
+    instruction_count: u64,
+    output_count: u64,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename = "State")]
+struct StateBuf {
+    r_a: usize,
+    r_c: usize,
+    r_d: usize,
+    mem: Vec<u16>,
+    stopped: bool,
+    instruction_count: u64,
+    output_count: u64,
+}
+
+impl Serialize for State {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let state = StateRef {
+            r_a: self.r_a,
+            r_c: self.r_c,
+            r_d: self.r_d,
+            mem: &self.mem[..],
+            stopped: self.stopped,
+            instruction_count: self.instruction_count,
+            output_count: self.output_count,
+        };
+
+        state.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for State {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<State, D::Error> {
+        let state = StateBuf::deserialize(deserializer)?;
+
+        if [state.r_a, state.r_c, state.r_d].iter().any(|&r| r >= MAX_MEMORY) {
+            return Err(de::Error::custom("register out of range"));
+        }
+
+        if state.mem.iter().any(|&value| value as usize >= MAX_MEMORY) {
+            return Err(de::Error::custom("memory cell out of range"));
+        }
+
+        let len = state.mem.len();
+        let mem: Box<Memory> = state.mem.try_into()
+            .map_err(|_| de::Error::invalid_length(len, &"59049 memory cells"))?;
+
+        Ok(State {
+            r_a: state.r_a,
+            r_c: state.r_c,
+            r_d: state.r_d,
+            mem,
+            stopped: state.stopped,
+            instruction_count: state.instruction_count,
+            output_count: state.output_count,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use crate::{Halt, Interpreter, State};
+
+    #[test]
+    fn round_trip_test() {
+        let source = include_bytes!("../programs/hello-world.mb");
+        let mut expected = Vec::new();
+        Interpreter::new(source).unwrap().execute(&mut io::empty(), &mut expected).unwrap();
+
+        // stop halfway through and save the machine
+        let mut interpreter = Interpreter::new(source).unwrap();
+        let mut output = Vec::new();
+        interpreter.add_breakpoint(20);
+        let exit = interpreter.execute(&mut io::empty(), &mut output).unwrap();
+        assert_eq!(exit.halt, Halt::Breakpoint(20));
+
+        let json = serde_json::to_string(&interpreter.save_state()).unwrap();
+        let state: State = serde_json::from_str(&json).unwrap();
+        assert_eq!(state, interpreter.save_state());
+
+        // resume in a different machine
+        let count = state.instruction_count;
+        assert!(count > 0);
+
+        let mut interpreter = Interpreter::new(source).unwrap();
+        interpreter.load_state(&state);
+        assert_eq!(interpreter.instruction_count(), count);
+        interpreter.execute(&mut io::empty(), &mut output).unwrap();
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn invalid_state_test() {
+        let json = r#"{"r_a":0,"r_c":0,"r_d":0,"mem":[1,2,3],"stopped":false,"instruction_count":0,"output_count":0}"#;
+        assert!(serde_json::from_str::<State>(json).is_err());
+    }
+}
@@ -0,0 +1,208 @@
+// MIT License
+//
+// Copyright (C) 2015-2018 Steve Sprang
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Keystroke-at-a-time input behind the `--raw-input` and `--echo` options.
+
+use std::io::{self, Read, Write};
+
+/// Puts the terminal on stdin into non-canonical mode, so each byte can be
+/// read as soon as it's typed, and turns off the terminal's own echo. The
+/// previous settings are restored when the guard is dropped. With the
+/// `terminal` feature on Unix they are also restored if the binary panics
+/// or is interrupted by a signal such as Ctrl-C; otherwise `stty` is used
+/// and an interrupt leaves the terminal as it was while running.
+pub struct RawMode {
+    saved: imp::Saved,
+}
+
+impl RawMode {
+    /// Returns `None` if the terminal settings couldn't be changed, e.g.
+    /// because stdin isn't a terminal or there is no `stty`.
+    pub fn enable() -> Option<RawMode> {
+        imp::enable().map(|saved| RawMode { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        imp::restore(&self.saved);
+    }
+}
+
+#[cfg(all(unix, feature = "terminal"))]
+use termios as imp;
+
+#[cfg(not(all(unix, feature = "terminal")))]
+use stty as imp;
+
+#[cfg(all(unix, feature = "terminal"))]
+mod termios {
+    use std::sync::{Once, OnceLock};
+
+    pub type Saved = libc::termios;
+
+    // the settings from before raw mode was first enabled, for the panic
+    // hook and signal handler
+    static ORIGINAL: OnceLock<libc::termios> = OnceLock::new();
+
+    pub fn enable() -> Option<libc::termios> {
+        // SAFETY: termios is plain data and tcgetattr fills it in
+        let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut saved) } != 0 {
+            return None;
+        }
+
+        ORIGINAL.get_or_init(|| saved);
+        install_handlers();
+
+        let mut raw = saved;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) } != 0 {
+            return None;
+        }
+
+        Some(saved)
+    }
+
+    pub fn restore(saved: &libc::termios) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, saved);
+        }
+    }
+
+    fn restore_original() {
+        if let Some(original) = ORIGINAL.get() {
+            restore(original);
+        }
+    }
+
+    /// Covers the ways out that skip `RawMode`'s `Drop`: a panic that
+    /// aborts, and the signals sent by Ctrl-C, Ctrl-\, `kill` and closing
+    /// the terminal.
+    fn install_handlers() {
+        static INSTALL: Once = Once::new();
+
+        INSTALL.call_once(|| {
+            let hook = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                restore_original();
+                hook(info);
+            }));
+
+            let handler = on_signal as extern "C" fn(libc::c_int);
+
+            for signal in [libc::SIGINT, libc::SIGQUIT, libc::SIGTERM, libc::SIGHUP] {
+                unsafe {
+                    // leave alone signals that were already being ignored,
+                    // as under nohup
+                    if libc::signal(signal, handler as libc::sighandler_t) == libc::SIG_IGN {
+                        libc::signal(signal, libc::SIG_IGN);
+                    }
+                }
+            }
+        });
+    }
+
+    extern "C" fn on_signal(signal: libc::c_int) {
+        // tcsetattr is async-signal-safe and ORIGINAL is only read here
+        restore_original();
+
+        // die of the same signal, as if there were no handler
+        unsafe {
+            libc::signal(signal, libc::SIG_DFL);
+            libc::raise(signal);
+        }
+    }
+}
+
+#[cfg(not(all(unix, feature = "terminal")))]
+mod stty {
+    use std::io;
+    use std::process::{Command, Output, Stdio};
+
+    pub type Saved = String;
+
+    pub fn enable() -> Option<String> {
+        let output = stty(&["-g"]).ok()?;
+        if !output.status.success() {
+            return None;
+        }
+
+        let saved = String::from_utf8(output.stdout).ok()?.trim().to_string();
+
+        match stty(&["-icanon", "-echo", "min", "1", "time", "0"]) {
+            Ok(output) if output.status.success() => Some(saved),
+            _ => None,
+        }
+    }
+
+    pub fn restore(saved: &str) {
+        let _ = stty(&[saved]);
+    }
+
+    /// Runs `stty` against the terminal on stdin.
+    fn stty(args: &[&str]) -> io::Result<Output> {
+        Command::new("stty")
+            .args(args)
+            .stdin(Stdio::inherit())
+            .stderr(Stdio::null())
+            .output()
+    }
+}
+
+/// Reads from `inner` one byte at a time, flushing stdout first so that any
+/// prompt is visible, and optionally echoing each byte read to stdout.
+pub struct Interactive<R> {
+    inner: R,
+    echo: bool,
+}
+
+impl<R: Read> Interactive<R> {
+    pub fn new(inner: R, echo: bool) -> Interactive<R> {
+        Interactive { inner, echo }
+    }
+}
+
+impl<R: Read> Read for Interactive<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut stdout = io::stdout();
+        stdout.flush()?;
+
+        let n = self.inner.read(&mut buf[..1])?;
+
+        if self.echo && n > 0 {
+            stdout.write_all(&buf[..n])?;
+            stdout.flush()?;
+        }
+
+        Ok(n)
+    }
+}
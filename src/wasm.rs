@@ -0,0 +1,52 @@
+// MIT License
+//
+// Copyright (C) 2015-2018 Steve Sprang
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! JavaScript bindings, built with the `wasm` feature.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{Config, Halt, Interpreter};
+
+/// Stops programs that would otherwise hang the browser.
+const MAX_STEPS: u64 = 100_000_000;
+
+/// Runs `source` to completion, feeding it the bytes of `input`, and
+/// returns everything it printed. Output that isn't valid UTF-8 is
+/// replaced with U+FFFD.
+#[wasm_bindgen]
+pub fn run_program(source: &str, input: &str) -> Result<String, JsValue> {
+    let config = Config { max_steps: Some(MAX_STEPS), ..Config::default() };
+    let mut interpreter = Interpreter::with_config(source.as_bytes(), config)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let mut output = Vec::new();
+    let exit = interpreter.execute(&mut input.as_bytes(), &mut output)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    match exit.halt {
+        Halt::Stopped | Halt::FellThrough { .. } =>
+            Ok(String::from_utf8_lossy(&output).into_owned()),
+        _ => Err(JsValue::from_str("Step limit reached.")),
+    }
+}
@@ -0,0 +1,74 @@
+// MIT License
+//
+// Copyright (C) 2015-2018 Steve Sprang
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Runs the binary to check what it reports and how it exits.
+
+#![cfg(feature = "cli")]
+
+use std::process::{Command, Output, Stdio};
+
+fn malbolge(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_malbolge"))
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn check() {
+    let output = malbolge(&["--check", "programs/hello-world.mb"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("Length: "));
+
+    // a program that can't be loaded fails, with the reason on stderr
+    for program in ["programs/invalid-char.mb", "programs/empty.mb", "programs/no-such-file.mb"] {
+        let output = malbolge(&["--check", program]);
+        assert_eq!(output.status.code(), Some(1), "{}", program);
+        assert!(output.stdout.is_empty(), "{}", program);
+        assert!(!output.stderr.is_empty(), "{}", program);
+    }
+}
+
+#[test]
+fn load_image() {
+    let output = malbolge(&["--load-image", "programs/no-such-image"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("Could not load memory image."));
+}
+
+#[test]
+fn continue_on_error() {
+    let programs = ["programs/invalid-char.mb", "programs/hello-world.mb"];
+
+    // the batch stops at the first failure
+    let output = malbolge(&programs);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("hello-world.mb"));
+
+    // or runs to the end, but still fails
+    let output = malbolge(&[&["--continue-on-error"], &programs[..]].concat());
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("==> programs/hello-world.mb <=="));
+}
@@ -0,0 +1,26 @@
+/*
+ * The C side of tests/ffi.rs. It only reaches the library through
+ * include/malbolge.h, so a header that has drifted from src/ffi.rs fails
+ * to compile or fails the test. build.rs compiles it with the `ffi`
+ * feature.
+ */
+
+#include "malbolge.h"
+
+intptr_t ffi_test_run(const unsigned char *source, size_t source_len,
+                      const unsigned char *input, size_t input_len,
+                      unsigned char *out, size_t out_cap) {
+    return mal_run(source, source_len, input, input_len, out, out_cap);
+}
+
+intptr_t ffi_test_run_limited(const unsigned char *source, size_t source_len,
+                              const unsigned char *input, size_t input_len,
+                              unsigned char *out, size_t out_cap,
+                              uint64_t max_steps) {
+    return mal_run_limited(source, source_len, input, input_len, out, out_cap, max_steps);
+}
+
+/* The MAL_ERR_* codes, in order. */
+const intptr_t ffi_test_errors[] = {
+    MAL_ERR_NULL, MAL_ERR_INIT, MAL_ERR_OUTPUT, MAL_ERR_IO, MAL_ERR_STEP_LIMIT,
+};
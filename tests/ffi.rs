@@ -0,0 +1,98 @@
+// MIT License
+//
+// Copyright (C) 2015-2018 Steve Sprang
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+#![cfg(feature = "ffi")]
+
+use malbolge::ffi::*;
+
+// tests/ffi.c, compiled against include/malbolge.h by build.rs; it calls
+// into this crate's `mal_run` and `mal_run_limited`
+#[link(name = "malbolge_ffi_test", kind = "static")]
+extern "C" {
+    fn ffi_test_run(source: *const u8,
+                    source_len: usize,
+                    input: *const u8,
+                    input_len: usize,
+                    out: *mut u8,
+                    out_cap: usize) -> isize;
+    fn ffi_test_run_limited(source: *const u8,
+                            source_len: usize,
+                            input: *const u8,
+                            input_len: usize,
+                            out: *mut u8,
+                            out_cap: usize,
+                            max_steps: u64) -> isize;
+    static ffi_test_errors: [isize; 5];
+}
+
+fn run(source: &[u8], input: &[u8], out: &mut [u8]) -> isize {
+    unsafe {
+        ffi_test_run(source.as_ptr(), source.len(),
+                     input.as_ptr(), input.len(),
+                     out.as_mut_ptr(), out.len())
+    }
+}
+
+fn run_for(source: &[u8], input: &[u8], out: &mut [u8], max_steps: u64) -> isize {
+    unsafe {
+        ffi_test_run_limited(source.as_ptr(), source.len(),
+                             input.as_ptr(), input.len(),
+                             out.as_mut_ptr(), out.len(),
+                             max_steps)
+    }
+}
+
+#[test]
+fn hello_world() {
+    let source = include_bytes!("fixtures/hello-world.mb");
+    let mut out = [0u8; 64];
+
+    let len = run(source, b"", &mut out);
+    assert_eq!(&out[..len as usize], b"Hello World!");
+
+    // a limit the program stays under changes nothing, and 0 is no limit
+    for max_steps in [1000, 0] {
+        let len = run_for(source, b"", &mut out, max_steps);
+        assert_eq!(&out[..len as usize], b"Hello World!");
+    }
+}
+
+#[test]
+fn errors() {
+    let source = include_bytes!("fixtures/hello-world.mb");
+
+    assert_eq!(run(b"", b"", &mut [0u8; 64]), MAL_ERR_INIT);
+    assert_eq!(run(source, b"", &mut [0u8; 5]), MAL_ERR_OUTPUT);
+    assert_eq!(run_for(source, b"", &mut [0u8; 64], 10), MAL_ERR_STEP_LIMIT);
+
+    let len = unsafe { ffi_test_run(std::ptr::null(), 10, std::ptr::null(), 0,
+                                    std::ptr::null_mut(), 0) };
+    assert_eq!(len, MAL_ERR_NULL);
+}
+
+#[test]
+fn header_error_codes() {
+    let errors = unsafe { ffi_test_errors };
+    assert_eq!(errors, [MAL_ERR_NULL, MAL_ERR_INIT, MAL_ERR_OUTPUT, MAL_ERR_IO, MAL_ERR_STEP_LIMIT]);
+}
@@ -0,0 +1,155 @@
+// MIT License
+//
+// Copyright (C) 2015-2018 Steve Sprang
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Runs known programs against their expected output.
+//!
+//! `increment.mb` reads one byte and prints the next one. It stores the
+//! input crazy'd with 121 in a cell and rotates it five times, which moves
+//! the input's trits to the top, so each input gets its own table cell 243
+//! apart. `i` jumps through that cell into a run of `p` instructions that
+//! ends in `<`, and the cells after the table cell hold the operands that
+//! turn the input into the output. It handles every byte below 153, which
+//! covers ASCII.
+//!
+//! `busy-loop.mb` fills all of memory, mostly with the unprintable byte
+//! 0x80. A single `i` at 129 jumps to itself whenever `r_d` points at one
+//! of those bytes, so each pass of `r_d` through memory takes about 59049
+//! instructions. Once a pass, one cell is re-encrypted, and on the tenth
+//! pass its value sends the jump into a `v`. It does no I/O and executes
+//! exactly `BUSY_LOOP_INSTRUCTIONS`, which the benchmarks rely on.
+
+use std::io;
+
+use malbolge::{Config, Halt, Interpreter};
+
+const MAX_STEPS: u64 = 10_000_000;
+
+const BUSY_LOOP_INSTRUCTIONS: u64 = 590_731;
+
+struct Case {
+    name: &'static str,
+    source: &'static [u8],
+    input: &'static [u8],
+    output: &'static [u8],
+    halt: Halt,
+    /// Programs that never halt on their own are stopped after this much
+    /// output.
+    max_output: Option<usize>,
+}
+
+const CASES: &[Case] = &[
+    Case {
+        name: "hello world",
+        source: include_bytes!("fixtures/hello-world.mb"),
+        input: b"",
+        output: b"Hello World!",
+        halt: Halt::Stopped,
+        max_output: None,
+    },
+    Case {
+        name: "cat",
+        source: include_bytes!("fixtures/cat.mb"),
+        input: b"Malbolge\n",
+        output: b"Malbolge\n",
+        halt: Halt::OutputLimitReached,
+        max_output: Some(9),
+    },
+    Case {
+        name: "increment a",
+        source: include_bytes!("fixtures/increment.mb"),
+        input: b"a",
+        output: b"b",
+        halt: Halt::Stopped,
+        max_output: None,
+    },
+    Case {
+        name: "increment Z",
+        source: include_bytes!("fixtures/increment.mb"),
+        input: b"Z",
+        output: b"[",
+        halt: Halt::Stopped,
+        max_output: None,
+    },
+    Case {
+        name: "increment 0",
+        source: include_bytes!("fixtures/increment.mb"),
+        input: b"0",
+        output: b"1",
+        halt: Halt::Stopped,
+        max_output: None,
+    },
+    Case {
+        name: "increment 9",
+        source: include_bytes!("fixtures/increment.mb"),
+        input: b"9",
+        output: b":",
+        halt: Halt::Stopped,
+        max_output: None,
+    },
+    Case {
+        name: "increment newline",
+        source: include_bytes!("fixtures/increment.mb"),
+        input: b"\n",
+        output: b"\x0b",
+        halt: Halt::Stopped,
+        max_output: None,
+    },
+    Case {
+        name: "increment tilde",
+        source: include_bytes!("fixtures/increment.mb"),
+        input: b"~",
+        output: b"\x7f",
+        halt: Halt::Stopped,
+        max_output: None,
+    },
+];
+
+#[test]
+fn programs() {
+    for case in CASES {
+        let config = Config {
+            max_steps: Some(MAX_STEPS),
+            max_output: case.max_output,
+            ..Config::default()
+        };
+
+        let mut interpreter = Interpreter::with_config(case.source, config).unwrap();
+        let mut output = Vec::new();
+        let exit = interpreter.execute(&mut &case.input[..], &mut output).unwrap();
+
+        assert_eq!(output, case.output, "{}", case.name);
+        assert_eq!(exit.halt, case.halt, "{}", case.name);
+    }
+}
+
+#[test]
+fn busy_loop() {
+    let mut interpreter = Interpreter::new(include_bytes!("fixtures/busy-loop.mb")).unwrap();
+    let mut output = Vec::new();
+    let exit = interpreter.execute(&mut io::empty(), &mut output).unwrap();
+
+    assert_eq!(exit.halt, Halt::Stopped);
+    assert!(output.is_empty());
+    assert_eq!(interpreter.instruction_count(), BUSY_LOOP_INSTRUCTIONS);
+}
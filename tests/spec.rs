@@ -0,0 +1,116 @@
+// MIT License
+//
+// Copyright (C) 2015-2018 Steve Sprang
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Checks the arithmetic against Ben Olmstead's specification and the C
+//! reference interpreter that comes with it, rather than against the
+//! crate's own tables.
+
+use malbolge::{crazy_op, tri_rotate, Interpreter, MAX_MEMORY, XLAT1, XLAT2};
+
+/// The spec's crazy operation on one trit. Rows are the trit of [d],
+/// columns the trit of A.
+const TABLE: [[usize; 3]; 3] = [
+    [1, 0, 0],
+    [1, 0, 2],
+    [2, 2, 1],
+];
+
+/// Parses a ternary word written most significant trit first, as the spec
+/// writes them.
+fn trits(s: &str) -> usize {
+    s.bytes().fold(0, |n, t| n * 3 + (t - b'0') as usize)
+}
+
+#[test]
+fn crazy_op_example() {
+    // the worked example from the spec, 0001112220 crz 0120120120 =
+    // 1001022211, where the first operand is [d] and the second is A
+    let d = trits("0001112220");
+    let a = trits("0120120120");
+    assert_eq!(crazy_op(a, d), trits("1001022211"));
+}
+
+/// `a crz d` worked out a trit at a time with `TABLE`.
+fn crz(mut a: usize, mut d: usize) -> usize {
+    let mut result = 0;
+
+    for power in (0..10).map(|k| 3usize.pow(k)) {
+        result += TABLE[d % 3][a % 3] * power;
+        a /= 3;
+        d /= 3;
+    }
+
+    result
+}
+
+#[test]
+fn crazy_op_table() {
+    // the nine upper trits are all 0 crz 0, which is 1
+    let upper = trits("1111111110");
+
+    for (d, row) in TABLE.iter().enumerate() {
+        for (a, &expected) in row.iter().enumerate() {
+            assert_eq!(crazy_op(a, d), upper + expected, "{} crz {}", d, a);
+        }
+    }
+}
+
+#[test]
+fn rotate_examples() {
+    // the spec rotates the word at [d] one trit to the right, moving the
+    // least significant trit to the top
+    assert_eq!(tri_rotate(trits("0000211111")), trits("1000021111"));
+    assert_eq!(tri_rotate(trits("0000000001")), trits("1000000000"));
+    assert_eq!(tri_rotate(trits("0000000002")), trits("2000000000"));
+    assert_eq!(tri_rotate(trits("2222222220")), trits("0222222222"));
+}
+
+#[test]
+fn translation_tables() {
+    // copied from the spec; the first decodes instructions and the second
+    // re-encrypts them
+    assert_eq!(&XLAT1[..], &b"+b(29e*j1VMEKLyC})8&m#~W>qxdRp0wkrUo[D7,XTcA\"lI\
+                              .v%{gJh4G\\-=O@5`_3i<?Z';FNQuY]szf$!BS/|t:Pn6^Ha"[..]);
+    assert_eq!(&XLAT2[..], &b"5z]&gqtyfr$(we4{WP)H-Zn,[%\\3dL+Q;>U!pJS72FhOA1C\
+                              B6v^=I_0/8|jsb9m<.TVac`uY*MK'X~xDl}REokN:#?G\"i@"[..]);
+}
+
+#[test]
+fn loader_fill() {
+    // as in the reference, memory past the program is filled with
+    // [n] = [n-1] crz [n-2], worked out here without the crate's tables
+    let source = include_bytes!("fixtures/hello-world.mb");
+    let interpreter = Interpreter::new(source).unwrap();
+    let len = interpreter.instructions().count();
+
+    let mut mem: Vec<usize> = (0..len).map(|addr| interpreter.mem(addr)).collect();
+    for n in len..MAX_MEMORY {
+        mem.push(crz(mem[n - 1], mem[n - 2]));
+    }
+
+    assert!((0..MAX_MEMORY).all(|addr| interpreter.mem(addr) == mem[addr]));
+
+    // the last cell, which is where the fill has had longest to go wrong
+    assert_eq!(interpreter.mem(MAX_MEMORY - 1), 35);
+}
@@ -0,0 +1,101 @@
+// MIT License
+//
+// Copyright (C) 2015-2018 Steve Sprang
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+#![cfg(feature = "tracing")]
+
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use malbolge::Interpreter;
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+
+static TRACE: AtomicUsize = AtomicUsize::new(0);
+static DEBUG: AtomicUsize = AtomicUsize::new(0);
+static SPANS: AtomicUsize = AtomicUsize::new(0);
+static ENTERED: AtomicUsize = AtomicUsize::new(0);
+static OUTSIDE: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts trace and debug events, `execute` spans, and events outside of
+/// any span.
+struct Counter;
+
+impl Subscriber for Counter {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        if span.metadata().name() == "execute" {
+            SPANS.fetch_add(1, Ordering::Relaxed);
+        }
+
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        if ENTERED.load(Ordering::Relaxed) == 0 {
+            OUTSIDE.fetch_add(1, Ordering::Relaxed);
+        }
+
+        match *event.metadata().level() {
+            Level::TRACE => TRACE.fetch_add(1, Ordering::Relaxed),
+            Level::DEBUG => DEBUG.fetch_add(1, Ordering::Relaxed),
+            _ => 0,
+        };
+    }
+
+    fn enter(&self, _: &Id) {
+        ENTERED.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn exit(&self, _: &Id) {
+        ENTERED.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn events() {
+    let source = include_bytes!("fixtures/hello-world.mb");
+    let mut interpreter = Interpreter::new(source).unwrap();
+
+    tracing::subscriber::with_default(Counter, || {
+        interpreter.execute(&mut io::empty(), &mut io::sink()).unwrap();
+    });
+
+    // a trace event per instruction, including the final v that isn't
+    // counted, and a debug event per output byte plus one for the halt
+    let steps = interpreter.instruction_count() as usize;
+    assert_eq!(TRACE.load(Ordering::Relaxed), steps + 1);
+    assert_eq!(DEBUG.load(Ordering::Relaxed), "Hello World!".len() + 1);
+
+    // all of them inside the one span for the run
+    assert_eq!(SPANS.load(Ordering::Relaxed), 1);
+    assert_eq!(OUTSIDE.load(Ordering::Relaxed), 0);
+    assert_eq!(ENTERED.load(Ordering::Relaxed), 0);
+}